@@ -3,28 +3,84 @@
 
 use screencapturekit::cm::CMSampleBuffer;
 use screencapturekit::prelude::*;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
 use std::thread;
+use crate::CaptureError;
 
 const FFT_SIZE: usize = 2048;
+/// Failed starts in a row before giving up on SCK so the caller can fall back.
+const MAX_ATTEMPTS: u32 = 3;
 
 struct CaptureState {
     tx: mpsc::Sender<Vec<f32>>,
     buffer: Mutex<Vec<f32>>,
     frames_received: Arc<AtomicU64>,
+    sample_rate: Arc<AtomicU32>,
+    correlation: Arc<AtomicU32>,
+    /// What is being captured, for the capture config line.
+    source: String,
+    /// Set once the buffer layout of the first audio sample has been logged.
+    layout_logged: AtomicBool,
+    /// Set once a planar length mismatch has been logged, so it can't flood stderr.
+    mismatch_logged: AtomicBool,
+}
+
+impl CaptureState {
+    /// Logs the capture chain as actually delivered, once the first audio buffer shows it.
+    fn log_config(&self, channels: usize, format: &str) {
+        let rate = self.sample_rate.load(Ordering::Relaxed);
+        crate::log_capture_config("ScreenCaptureKit", &self.source, rate, channels, format);
+    }
 }
 
 struct AudioHandler {
     state: Arc<CaptureState>,
 }
 
+/// Channel count of an interleaved buffer holding `samples` values over `frames` frames.
+/// Some systems report `number_channels == 1` for stereo interleaved data, so when the
+/// sample count is a whole multiple of the frame count that ratio wins over `reported`.
+fn interleaved_channels(samples: usize, frames: usize, reported: usize) -> usize {
+    if frames > 0 && samples >= frames && samples % frames == 0 {
+        samples / frames
+    } else {
+        reported.max(1)
+    }
+}
+
+/// Decodes 32-bit float samples without assuming the buffer is 4-byte aligned.
+/// Returns `None` if the length isn't a whole number of samples.
+fn read_f32_samples(data: &[u8]) -> Option<Vec<f32>> {
+    if data.len() % 4 != 0 {
+        return None;
+    }
+    Some(
+        data.chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect(),
+    )
+}
+
 impl SCStreamOutputTrait for AudioHandler {
     fn did_output_sample_buffer(&self, sample: CMSampleBuffer, output_type: SCStreamOutputType) {
         if output_type != SCStreamOutputType::Audio {
             return;
         }
+        if let Some(fmt) = sample.format_description() {
+            if let Some(rate) = fmt.audio_sample_rate() {
+                // 44.1 kHz is only requested; report whatever SCK actually delivers
+                let previous = self.state.sample_rate.swap(rate as u32, Ordering::Relaxed);
+                if previous != rate as u32 {
+                    eprintln!("SCK delivering {} Hz audio (requested 44100)", rate);
+                }
+            }
+            // Everything below decodes 32-bit float; anything else would be garbage
+            if !fmt.audio_is_float() || fmt.audio_bits_per_channel() != Some(32) {
+                return;
+            }
+        }
         let Some(abl) = sample.audio_buffer_list() else {
             return;
         };
@@ -37,28 +93,63 @@ impl SCStreamOutputTrait for AudioHandler {
         if bytes_per_channel == 0 {
             return;
         }
-        let n_frames = bytes_per_channel / 4;
         if n_bufs >= 2 {
-            let b0 = abl.get(0).unwrap().data();
-            let b1 = abl.get(1).unwrap().data();
-            if b0.len() >= n_frames * 4 && b1.len() >= n_frames * 4 {
-                let s0 = unsafe { std::slice::from_raw_parts(b0.as_ptr() as *const f32, n_frames) };
-                let s1 = unsafe { std::slice::from_raw_parts(b1.as_ptr() as *const f32, n_frames) };
-                for i in 0..n_frames {
-                    mono.push((s0[i] + s1[i]) * 0.5);
-                }
+            // Non-interleaved: one buffer per channel. Their lengths occasionally differ by a
+            // few frames; mix up to the shortest rather than dropping the whole sample.
+            let mut planes: Vec<Vec<f32>> = Vec::with_capacity(n_bufs);
+            for i in 0..n_bufs {
+                let Some(b) = abl.get(i) else {
+                    return;
+                };
+                let data = b.data();
+                let len = data.len().min(b.data_byte_size()) / 4 * 4;
+                let Some(plane) = read_f32_samples(&data[..len]) else {
+                    return;
+                };
+                planes.push(plane);
+            }
+            let n_frames = planes.iter().map(Vec::len).min().unwrap_or(0);
+            if planes.iter().any(|p| p.len() != n_frames) && !self.state.mismatch_logged.swap(true, Ordering::Relaxed) {
+                let lengths: Vec<usize> = planes.iter().map(Vec::len).collect();
+                eprintln!(
+                    "SCK planar buffers differ in length ({:?} frames), mixing the first {} (logged once)",
+                    lengths, n_frames
+                );
+            }
+            if !self.state.layout_logged.swap(true, Ordering::Relaxed) {
+                eprintln!("SCK audio layout: planar, {} buffers of {} frames", n_bufs, n_frames);
+                self.state.log_config(n_bufs, "f32 planar");
+            }
+            let pairs = planes[0].iter().copied().zip(planes[1].iter().copied());
+            let correlation = crate::stereo_correlation(pairs).unwrap_or(f32::NAN);
+            self.state.correlation.store(correlation.to_bits(), Ordering::Relaxed);
+            let scale = 1.0 / n_bufs as f32;
+            for i in 0..n_frames {
+                mono.push(planes.iter().map(|p| p[i]).sum::<f32>() * scale);
             }
         } else {
             let b = abl.get(0).unwrap();
-            let ch = b.number_channels as usize;
-            let data = b.data();
-            let n_samps = data.len() / 4;
+            let reported = b.number_channels as usize;
+            let Some(s) = read_f32_samples(b.data()) else {
+                return;
+            };
+            // Don't trust number_channels alone; cross-check against the sample buffer's frame count
+            let ch = interleaved_channels(s.len(), sample.num_samples(), reported);
+            if !self.state.layout_logged.swap(true, Ordering::Relaxed) {
+                eprintln!(
+                    "SCK audio layout: interleaved, {} samples over {} frames, {} channel(s) reported, using {}",
+                    s.len(),
+                    sample.num_samples(),
+                    reported,
+                    ch
+                );
+                self.state.log_config(ch, "f32 interleaved");
+            }
+            crate::store_correlation(&self.state.correlation, &s, ch);
             if ch <= 1 {
-                let s = unsafe { std::slice::from_raw_parts(data.as_ptr() as *const f32, n_samps) };
-                mono.extend_from_slice(s);
+                mono.extend_from_slice(&s);
             } else {
-                let s = unsafe { std::slice::from_raw_parts(data.as_ptr() as *const f32, n_samps) };
-                for c in s.chunks(ch) {
+                for c in s.chunks_exact(ch) {
                     let v: f32 = c.iter().sum::<f32>() / ch as f32;
                     mono.push(v);
                 }
@@ -87,12 +178,23 @@ impl SCStreamOutputTrait for AudioHandler {
     }
 }
 
-/// Runs system audio capture via ScreenCaptureKit; reinitializes on error.
-pub fn capture_loopback(tx: mpsc::Sender<Vec<f32>>, frames_received: Arc<AtomicU64>) {
-    loop {
-        if let Err(e) = run_capture(tx.clone(), &frames_received) {
-            eprintln!("ScreenCaptureKit capture error: {:?}, reinitializing in 2s...", e);
-            thread::sleep(std::time::Duration::from_secs(2));
+/// Runs system audio capture via ScreenCaptureKit; reinitializes on error. Returns once SCK
+/// has failed to start `MAX_ATTEMPTS` times in a row (e.g. an unsupported macOS version or
+/// a permission that stays denied), so the caller can fall back to another backend.
+pub fn capture_loopback(
+    tx: mpsc::Sender<Vec<f32>>,
+    frames_received: Arc<AtomicU64>,
+    sample_rate: Arc<AtomicU32>,
+    correlation: Arc<AtomicU32>,
+) {
+    // run_capture only returns on a failed start; once running it captures for good
+    for attempt in 1..=MAX_ATTEMPTS {
+        if let Err(e) = run_capture(tx.clone(), &frames_received, &sample_rate, &correlation) {
+            if attempt == MAX_ATTEMPTS {
+                eprintln!("ScreenCaptureKit capture error: {}, giving up after {} attempts", e, attempt);
+                return;
+            }
+            thread::sleep(crate::capture_retry_delay("ScreenCaptureKit", &e));
         }
     }
 }
@@ -100,13 +202,27 @@ pub fn capture_loopback(tx: mpsc::Sender<Vec<f32>>, frames_received: Arc<AtomicU
 fn run_capture(
     tx: mpsc::Sender<Vec<f32>>,
     frames_received: &AtomicU64,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let content = SCShareableContent::get()?;
+    sample_rate: &Arc<AtomicU32>,
+    correlation: &Arc<AtomicU32>,
+) -> Result<(), CaptureError> {
+    let content = SCShareableContent::get().map_err(|e| {
+        let message = e.to_string();
+        // SCStreamErrorUserDeclined (-3801): Screen Recording hasn't been granted. Anything else,
+        // e.g. an unsupported OS, is passed on as is so it isn't blamed on permissions.
+        if message.contains("-3801") || message.to_lowercase().contains("declined") {
+            CaptureError::PermissionDenied(format!(
+                "grant Screen Recording to this app (or its terminal) in System Settings > Privacy & Security ({})",
+                message
+            ))
+        } else {
+            CaptureError::Backend(message)
+        }
+    })?;
     let display = content
         .displays()
         .into_iter()
         .next()
-        .ok_or("No displays found")?;
+        .ok_or_else(|| CaptureError::NoDevice("no displays found".into()))?;
 
     let filter = SCContentFilter::create()
         .with_display(&display)
@@ -124,6 +240,11 @@ fn run_capture(
         tx,
         buffer: Mutex::new(Vec::with_capacity(FFT_SIZE * 2)),
         frames_received: Arc::new(AtomicU64::new(0)),
+        sample_rate: Arc::clone(sample_rate),
+        correlation: Arc::clone(correlation),
+        source: format!("system audio (display {})", display.display_id()),
+        layout_logged: AtomicBool::new(false),
+        mismatch_logged: AtomicBool::new(false),
     });
     state
         .frames_received
@@ -136,8 +257,10 @@ fn run_capture(
     stream.add_output_handler(handler.clone(), SCStreamOutputType::Screen);
     stream.add_output_handler(handler, SCStreamOutputType::Audio);
 
-    eprintln!("Using macOS ScreenCaptureKit (display + system audio)");
-    stream.start_capture()?;
+    // The requested format, stored before the handler can run so it never overwrites the real
+    // rate; the actual rate is re-read from each sample buffer and logged if it differs
+    sample_rate.store(44100, Ordering::Relaxed);
+    stream.start_capture().map_err(|e| CaptureError::Backend(e.to_string()))?;
 
     loop {
         thread::sleep(std::time::Duration::from_millis(100));
@@ -1,82 +1,252 @@
 //! Windows loopback capture using wasapi - captures from default output (speakers)
 
 use std::collections::VecDeque;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use std::sync::mpsc;
 use std::thread;
-use wasapi::{Direction, DeviceEnumerator, SampleType, StreamMode, WaveFormat};
+use crate::config::Latency;
+use crate::CaptureError;
+use wasapi::{AudioClient, Direction, DeviceEnumerator, SampleType, StreamMode, WaveFormat};
 
 const FFT_SIZE: usize = 2048;
+/// How often the default output device is re-checked while capturing.
+const DEFAULT_DEVICE_POLL: std::time::Duration = std::time::Duration::from_secs(1);
 
-/// Runs capture in a loop; on stream errors, reinitializes and continues.
-pub fn capture_loopback(tx: mpsc::Sender<Vec<f32>>, frames_received: std::sync::Arc<AtomicU64>) {
+/// Why a capture session ended without an error.
+enum CaptureEnd {
+    /// The visualizer hung up; stop capturing.
+    Closed,
+    /// The user picked a different default output; capture that one instead.
+    DefaultChanged,
+}
+
+impl From<wasapi::WasapiError> for CaptureError {
+    fn from(e: wasapi::WasapiError) -> Self {
+        CaptureError::Backend(e.to_string())
+    }
+}
+
+/// Decodes interleaved little-endian samples of the negotiated format into f32.
+/// Returns `None` for layouts we don't know how to read.
+fn decode_samples(bytes: &[u8], sample_type: &SampleType, bytes_per_sample: usize) -> Option<Vec<f32>> {
+    let samples = match (sample_type, bytes_per_sample) {
+        (SampleType::Float, 4) => bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect(),
+        (SampleType::Int, 2) => bytes
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0)
+            .collect(),
+        (SampleType::Int, 3) => bytes
+            .chunks_exact(3)
+            .map(|b| (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f32 / 8_388_608.0)
+            .collect(),
+        // Also covers 24-bit valid samples in a 32-bit container (left-justified)
+        (SampleType::Int, 4) => bytes
+            .chunks_exact(4)
+            .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2_147_483_648.0)
+            .collect(),
+        _ => return None,
+    };
+    Some(samples)
+}
+
+/// Runs capture in a loop; on stream errors, reinitializes and continues. Follows the
+/// default output device when it changes (e.g. speakers to headphones). With a `pid`, or
+/// the first running `process` of that name, captures that process tree instead, falling
+/// back to the system mix if Windows can't.
+pub fn capture_loopback(
+    tx: mpsc::Sender<Vec<f32>>,
+    frames_received: std::sync::Arc<AtomicU64>,
+    sample_rate: std::sync::Arc<AtomicU32>,
+    correlation: std::sync::Arc<AtomicU32>,
+    latency: Latency,
+    pid: Option<u32>,
+    process: Option<&str>,
+) {
     wasapi::initialize_mta().ok().expect("COM init");
+    let pid = pid.or_else(|| {
+        let name = process?;
+        let pid = find_process(name);
+        if pid.is_none() {
+            eprintln!("No running process named {}, capturing system audio instead", name);
+        }
+        pid
+    });
 
     loop {
-        if let Err(e) = run_capture_loop(&tx, &frames_received) {
-            eprintln!("Loopback capture error: {:?}, reinitializing in 2s...", e);
-            thread::sleep(std::time::Duration::from_secs(2));
+        match run_capture_loop(&tx, &frames_received, &sample_rate, &correlation, latency, pid) {
+            Ok(CaptureEnd::Closed) => return,
+            Ok(CaptureEnd::DefaultChanged) => eprintln!("Default output device changed, switching capture"),
+            Err(e) => thread::sleep(crate::capture_retry_delay("Loopback", &e)),
         }
     }
 }
 
-fn run_capture_loop(
-    tx: &mpsc::Sender<Vec<f32>>,
-    frames_received: &AtomicU64,
-) -> Result<(), wasapi::WasapiError> {
-    let enumerator = DeviceEnumerator::new()?;
-    let device = enumerator.get_default_device(&Direction::Render)?;
+/// PID of the first running process whose image name is `name` (case-insensitive), via
+/// `tasklist`. Apps like browsers run many processes; the first is usually the parent, and
+/// process loopback includes its children.
+fn find_process(name: &str) -> Option<u32> {
+    let filter = format!("IMAGENAME eq {}", name);
+    let output = std::process::Command::new("tasklist")
+        .args(["/FI", filter.as_str(), "/FO", "CSV", "/NH"])
+        .output()
+        .ok()?;
+    // Rows look like "spotify.exe","1234","Console","1","120,412 K"; no match prints an INFO line
+    String::from_utf8_lossy(&output.stdout).lines().find_map(|line| {
+        let mut fields = line.trim().trim_matches('"').split("\",\"");
+        let image = fields.next()?;
+        if !image.eq_ignore_ascii_case(name) {
+            return None;
+        }
+        fields.next()?.parse().ok()
+    })
+}
+
+/// Opens process loopback capture of `pid` and its children. The virtual device only takes
+/// an explicit format with autoconvert and has no period or mix format to query.
+fn open_process_loopback(pid: u32) -> Result<(AudioClient, WaveFormat), CaptureError> {
+    let mut audio_client = AudioClient::new_application_loopback_client(pid, true)?;
+    let format = WaveFormat::new(32, 32, &SampleType::Float, 44100, 2, None);
+    let mode = StreamMode::EventsShared {
+        autoconvert: true,
+        buffer_duration_hns: 0,
+    };
+    audio_client.initialize_client(&format, &Direction::Capture, &mode)?;
+    crate::log_capture_config("WASAPI process loopback", &format!("PID {}", pid), 44100, 2, "Float 32-bit");
+    Ok((audio_client, format))
+}
+
+/// Opens loopback capture of the default output device, preferring 44.1 kHz float.
+/// Returns the client, its negotiated format and the device id.
+fn open_system_loopback(
+    enumerator: &DeviceEnumerator,
+    latency: Latency,
+) -> Result<(AudioClient, WaveFormat, String), CaptureError> {
+    let device = enumerator
+        .get_default_device(&Direction::Render)
+        .map_err(|e| CaptureError::NoDevice(format!("no default output device ({})", e)))?;
+    let device_id = device.get_id()?;
     let device_name = device.get_friendlyname().unwrap_or_else(|_| "Unknown".into());
-    println!("Using audio device: {} (loopback)", device_name);
 
     let mut audio_client = device.get_iaudioclient()?;
     let desired_format = WaveFormat::new(32, 32, &SampleType::Float, 44100, 2, None);
-    let blockalign = desired_format.get_blockalign() as usize;
 
-    let (_def_time, min_time) = audio_client.get_device_period()?;
+    let (def_time, min_time) = audio_client.get_device_period()?;
+    // Shared mode already runs at the minimum period, so only `high` changes anything
+    let buffer_duration_hns = match latency {
+        Latency::Low | Latency::Normal => min_time,
+        Latency::High => def_time * 4,
+    };
     let mode = StreamMode::EventsShared {
         autoconvert: true,
-        buffer_duration_hns: min_time,
+        buffer_duration_hns,
+    };
+    // autoconvert makes the engine deliver exactly the requested format when it can;
+    // some pro-audio drivers refuse, so fall back to the device's native mix format.
+    let format = match audio_client.initialize_client(&desired_format, &Direction::Capture, &mode) {
+        Ok(()) => desired_format,
+        Err(e) => {
+            eprintln!("Float capture format rejected ({:?}), using device mix format", e);
+            audio_client = device.get_iaudioclient()?;
+            let mix_format = audio_client.get_mixformat()?;
+            let native_mode = StreamMode::EventsShared {
+                autoconvert: false,
+                buffer_duration_hns,
+            };
+            audio_client.initialize_client(&mix_format, &Direction::Capture, &native_mode)?;
+            mix_format
+        }
+    };
+    crate::log_capture_config(
+        "WASAPI loopback",
+        &device_name,
+        format.get_samplespersec(),
+        format.get_nchannels() as usize,
+        &format!("{:?} {}-bit", format.get_subformat()?, format.get_bitspersample()),
+    );
+    Ok((audio_client, format, device_id))
+}
+
+fn run_capture_loop(
+    tx: &mpsc::Sender<Vec<f32>>,
+    frames_received: &AtomicU64,
+    sample_rate: &AtomicU32,
+    correlation: &AtomicU32,
+    latency: Latency,
+    pid: Option<u32>,
+) -> Result<CaptureEnd, CaptureError> {
+    let enumerator = DeviceEnumerator::new()?;
+    // Process audio is captured whichever device it plays on, so only system capture
+    // tracks the default device
+    let (audio_client, format, device_id) = match pid.map(open_process_loopback) {
+        Some(Ok((client, format))) => (client, format, None),
+        Some(Err(e)) => {
+            eprintln!("Process loopback unavailable ({:?}), capturing system audio instead", e);
+            let (client, format, id) = open_system_loopback(&enumerator, latency)?;
+            (client, format, Some(id))
+        }
+        None => {
+            let (client, format, id) = open_system_loopback(&enumerator, latency)?;
+            (client, format, Some(id))
+        }
     };
-    audio_client.initialize_client(&desired_format, &Direction::Capture, &mode)?;
+    let sample_type = format.get_subformat()?;
+    let bytes_per_sample = format.get_bitspersample() as usize / 8;
+    let channels = format.get_nchannels() as usize;
+    let blockalign = format.get_blockalign() as usize;
+    if decode_samples(&[], &sample_type, bytes_per_sample).is_none() {
+        return Err(CaptureError::FormatUnsupported(format!(
+            "{:?} {}-bit",
+            sample_type,
+            format.get_bitspersample()
+        )));
+    }
+    sample_rate.store(format.get_samplespersec(), Ordering::Relaxed);
 
     let h_event = audio_client.set_get_eventhandle()?;
     let buffer_frame_count = audio_client.get_buffer_size()?;
     let capture_client = audio_client.get_audiocaptureclient()?;
 
     let mut sample_queue: VecDeque<u8> = VecDeque::with_capacity(
-        blockalign as usize * (1024 + 2 * buffer_frame_count as usize),
+        blockalign * (1024 + 2 * buffer_frame_count as usize),
     );
     audio_client.start_stream()?;
-
-    let channels = 2;
+    // The wasapi crate doesn't wrap IMMNotificationClient, so default changes are polled
+    let mut last_default_check = std::time::Instant::now();
 
     loop {
-        while sample_queue.len() >= blockalign as usize * FFT_SIZE {
-            let mut chunk = vec![0u8; blockalign as usize * FFT_SIZE];
-            for (_, v) in chunk.iter_mut().enumerate() {
+        if device_id.is_some() && last_default_check.elapsed() >= DEFAULT_DEVICE_POLL {
+            last_default_check = std::time::Instant::now();
+            let default_id = enumerator.get_default_device(&Direction::Render).and_then(|d| d.get_id());
+            if default_id.is_ok_and(|id| device_id.as_ref() != Some(&id)) {
+                audio_client.stop_stream()?;
+                return Ok(CaptureEnd::DefaultChanged);
+            }
+        }
+
+        while sample_queue.len() >= blockalign * FFT_SIZE {
+            let mut chunk = vec![0u8; blockalign * FFT_SIZE];
+            for v in chunk.iter_mut() {
                 *v = sample_queue.pop_front().unwrap_or(0);
             }
-            let samples: Vec<f32> = chunk
-                .chunks(4)
-                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
-                .collect();
+            let samples = decode_samples(&chunk, &sample_type, bytes_per_sample).unwrap_or_default();
+            crate::store_correlation(correlation, &samples, channels);
             let mono: Vec<f32> = samples
                 .chunks(channels)
                 .map(|c| c.iter().sum::<f32>() / channels as f32)
                 .collect();
             let peak = mono.iter().map(|s| s.abs()).fold(0.0f32, f32::max);
             if tx.send(mono).is_err() {
-                return Ok(());
+                return Ok(CaptureEnd::Closed);
             }
             if peak >= 1e-6 {
                 frames_received.fetch_add(1, Ordering::Relaxed);
             }
         }
 
-        if let Err(e) = capture_client.read_from_device_to_deque(&mut sample_queue) {
-            return Err(e);
-        }
+        capture_client.read_from_device_to_deque(&mut sample_queue)?;
         if h_event.wait_for_event(100).is_err() {
             thread::sleep(std::time::Duration::from_millis(10));
             continue;
@@ -0,0 +1,1263 @@
+//! Command-line options
+
+use std::process;
+use std::sync::OnceLock;
+
+const USAGE: &str = "\
+Usage: audio-visualizer [OPTIONS]
+
+Options:
+  --latency <low|normal|high>  Capture buffer size (default: normal)
+  --spectral-smoothing <N>     Smooth the spectrum across N neighboring bins (0-3, default: 0)
+  --bar-smoothing <X>          Blend each drawn bar toward its neighbors by X, 0-1 (default: 0)
+  --bar-smoothing-wrap         Let --bar-smoothing blend the first and last bars on the full circle
+  --spectrum-average <N>       Average the raw spectrum over the last N FFT frames (1-64, default: 1)
+  --average-overlay            Draw the averaged spectrum as a contour over live bars (16 frames
+                               unless --spectrum-average is larger)
+  --bar-colors <palette|gradient|rise>
+                               Stepped palette, a continuous blue-to-red hue, or palette colors
+                               that flash hotter on fast rises (default: palette)
+  --palettes <FILE>            Load extra bar palettes, one name = [RRGGBB, ...] list per line; P cycles them
+  --rise-sensitivity <X>       How strongly rising bars flash in rise coloring (default: 4.0)
+  --color-gamma <X>            Curve applied to height before picking a palette color (default: 1.0)
+  --attack <SECONDS>           Time constant for bars rising toward a louder level (default: 0.05)
+  --release <SECONDS>          Time constant for bars falling back (default: 0.2)
+  --reference-pitch <HZ>       Tuning of A4 for the note readout, 400-480 (default: 440)
+  --min-freq <HZ>              Lowest frequency shown, below the bass/mid edge (default: 20)
+  --max-freq <HZ>              Highest frequency shown, above the mid/high edge up to Nyquist
+                               (default: 15000)
+  --band-reduce <rms|mix|max>  Reduce each bar's bins by power (RMS), a max/mean mix or the loudest
+                               bin (default: mix)
+  --bands <BASS,MID,HIGH>      Bars given to the bass, mid and high regions, summing to 64
+                               (default: 10,44,10)
+  --band-edges <LO,HI>         Bass/mid and mid/high boundaries in Hz (default: 500,3500)
+  --width <PX>                 Initial window width (default: 1024)
+  --height <PX>                Initial window height (default: 768)
+  --position <X,Y>             Initial window position on screen
+  --fullscreen                 Start in fullscreen
+  --max-fps <N>                Cap the frame rate below the display's refresh rate (default: vsync only)
+  --mode <circle|half>         Bar layout: full ring or bottom half-circle (default: circle)
+  --reactive-radius <MIN,MAX>  Let the inner circle breathe between MIN and MAX px with loudness
+  --inward                     Anchor bars at the outer edge and grow them toward the center
+  --pulse                      Brighten the scene briefly on onsets (spectral flux)
+  --pulse-sensitivity <X>      Flux must exceed its average by this factor (default: 1.5)
+  --pulse-decay <SECONDS>      Pulse fade time constant (default: 0.15)
+  --transient-flash <X>        Flash a bar toward white by up to X on a sharp attack, 0-1 (default: 0, off)
+  --transient-threshold <X>    Jump above a bar's height needed to fire a projectile (default: 0.025)
+  --peak-hysteresis <X>        Margin above the current peak that counts as a new peak (default: 0.05)
+  --projectile-colors <rainbow|fixed|band|bar>
+                               Starting projectile coloring (default: rainbow)
+  --projectile-motion <accel|decel>
+                               Projectiles speed up or slow down toward the edge (default: accel)
+  --projectile-slowdown <time|distance>
+                               Slowdown driven by time alive or distance to the edge (default: time)
+  --projectile-shape <square|circle>
+                               Draw projectiles and trails as squares or circles (default: square)
+  --min-speed <PX>             Speed in px/s decelerating projectiles ease down to (at least 10, default: 90)
+  --launch-from <base|tip>     Launch projectiles from the inner circle or the bar peaks (default: base)
+  --trail-alpha <MIN,MAX>      Opacity of projectile trails from tail to head (default: 0.04,0.74)
+  --trail-curve <POWER>        How sharply trails fade toward the tail; 1 is linear (default: 2)
+  --gravity-well               Pull projectiles toward the center so they orbit and spiral in
+  --vignette                   Darken the screen edges in quiet passages, opening up when loud
+  --max-spawns <N>             Most projectiles launched per frame, loudest bands first (default: no limit)
+  --max-lifetime <SECS>        Fade out and remove projectiles this many seconds after launch (default: none)
+  --fire-on <transients|transients+bass|beats>
+                               What fires projectiles: bar transients, those plus every bass peak, or
+                               detected beats (default: transients+bass)
+  --beats-only                 Same as --fire-on beats
+  --beat-burst <N>             Projectiles launched per beat from the loudest bars (default: 6)
+  --beat-hue-step <DEG>        Advance the rainbow hue by DEG on each beat instead of drifting
+  --second-ring <off|bars|peaks>
+                               Draw a second concentric ring showing bars or peaks (default: off)
+  --second-ring-radius <PX>    Inner radius of the second ring (default: 20)
+  --second-ring-direction <same|reverse>
+                               Spin the second ring with or against the main ring (default: reverse)
+  --input <->                  Read raw interleaved f32 little-endian PCM from stdin
+  --channels <N>               Channel count of --input PCM (default: 2)
+  --rate <HZ>                  Sample rate of --input or --listen PCM (default: 44100)
+  --listen <PORT>              Receive PCM packets over UDP on PORT instead of capturing
+  --serve <PORT>               Run headless and stream captured audio to visualizers over TCP
+  --connect <HOST:PORT>        Visualize audio streamed by a --serve instance instead of capturing
+  --line-in                    Capture from the default input device (line-in, aux, mic)
+  --monitor                    With --line-in, also play the input through the default output
+  --jack                       Capture through JACK input ports (builds with the `jack` feature)
+  --pid <ID>                   Windows: capture only process ID's audio (and its children's)
+  --process <NAME>             Windows: like --pid, for the first running process named NAME (e.g. spotify.exe)
+  --overlay <CORNER>           Status overlay corner: top-left, top-right, bottom-left or
+                               bottom-right (default: top-left)
+  --inset <off|spectrum|waveform>
+                               Small second view in a corner of the screen (default: off)
+  --inset-corner <CORNER>      Corner of the inset, as for --overlay (default: bottom-right)
+  --inset-size <X>             Inset width and height as a fraction of the window's, 0.1-0.5 (default: 0.25)
+  --ui-scale <X|auto>          Scale overlay text, 0.5-4, or auto from the window height (default: auto)
+  --start-angle <DEG>          Screen angle of bar 0's leading edge, clockwise from 3 o'clock
+                               (default: -45)
+  --winding <cw|ccw>           Direction bars run from bass to treble around the circle (default: cw)
+  --rotate <LEVEL>             Start rotating at speed LEVEL, -10 to 10 in 9°/s steps (default: off)
+  --auto-rotate                Start rotating with speed following the music's energy
+  --beat-align                 While rotating, nudge the reference bar toward the top on each beat
+  --beat-align-bar <N>         Reference bar for --beat-align, 0-63 (default: 0)
+  --intensity <X>              Starting intensity, 0.25-2.0 (default: 1.0)
+  --gain <X>                   Extra input gain on top of the automatic level normalization (default: 1.0)
+  --segments <N>               Draw bars as N stacked LED segments, 0 for solid bars (max 64, default: 0)
+  --dead-zone <X>              Draw bars below this fraction of full height as empty (0-0.2, default: 0)
+  --bar-gap <X>                Gap on each side of a bar as a fraction of its slot, 0-0.45 (default: 0.04)
+  --peak-cap-scale <X>         Grow peak caps by up to this fraction at full height (default: 0.5)
+  --peak-color <RRGGBB|auto>   Color of the peak-cap markers; auto contrasts with each bar (default: ffffff)
+  --peak-hit-glow <X>          Brighten and thicken a peak cap by up to X as its bar fires, 0-1 (default: 0)
+  --circle-color <RRGGBB>      Color of the inner circle (default: 828282)
+  --circle-thickness <PX>      Line width of the inner circle (default: 2)
+  --reactive-circle            Color the inner circle by level: hue from bass, brightness from loudness
+  --dropout-hold <SECONDS>     Hold the bars through silent chunks this soon after audio (default: 0.1)
+  --silence-timeout <SECONDS>  After this long without audio, fade to an empty, stopped ring
+  --selftest                   Visualize a synthetic sine sweep instead of captured audio
+  --bench-fft <N>              Time N analyzer updates on noise and print the throughput, no window
+  --print-bands                Print each bar's frequency span and FFT bins, then exit
+  -h, --help                   Print this help";
+
+/// Requested capture buffer size. Smaller buffers react faster at the cost of CPU.
+///
+/// Honored by the cpal backend (Linux and `--line-in`) and by WASAPI (Windows, `high` only: shared
+/// mode already runs at the device minimum period for `normal`). ScreenCaptureKit
+/// has no buffer setting and ignores it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Latency {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl Latency {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "low" => Ok(Self::Low),
+            "normal" => Ok(Self::Normal),
+            "high" => Ok(Self::High),
+            _ => Err(format!("invalid latency '{}', expected low, normal or high", s)),
+        }
+    }
+
+    /// Fixed buffer size in frames for cpal; `None` keeps the device default.
+    pub fn cpal_frames(self) -> Option<u32> {
+        match self {
+            Self::Low => Some(256),
+            Self::Normal => None,
+            Self::High => Some(4096),
+        }
+    }
+}
+
+/// Bar layout.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RenderMode {
+    /// Full 360° ring around the window center.
+    #[default]
+    Circle,
+    /// 180° arc anchored at the bottom edge, bars fanning upward.
+    HalfCircle,
+}
+
+impl RenderMode {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "circle" => Ok(Self::Circle),
+            "half" => Ok(Self::HalfCircle),
+            _ => Err(format!("invalid mode '{}', expected circle or half", s)),
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Self::Circle => Self::HalfCircle,
+            Self::HalfCircle => Self::Circle,
+        }
+    }
+}
+
+/// How bar colors are picked from bar height.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BarColors {
+    /// The 8-step classic palette.
+    #[default]
+    Palette,
+    /// Continuous hue sweep from blue (quiet) to red (loud).
+    Gradient,
+    /// Palette colors, flashing hotter while a bar is rising fast.
+    Rise,
+    /// The palette at this index in `Config::palettes`.
+    Custom(usize),
+}
+
+impl BarColors {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "palette" => Ok(Self::Palette),
+            "gradient" => Ok(Self::Gradient),
+            "rise" => Ok(Self::Rise),
+            _ => Err(format!("invalid bar colors '{}', expected palette, gradient or rise", s)),
+        }
+    }
+
+    /// The following mode, stepping through the `custom` loaded palettes after the built-ins.
+    pub fn next(self, custom: usize) -> Self {
+        match self {
+            Self::Palette => Self::Gradient,
+            Self::Gradient => Self::Rise,
+            Self::Rise | Self::Custom(_) => {
+                let i = if let Self::Custom(i) = self { i + 1 } else { 0 };
+                if i < custom {
+                    Self::Custom(i)
+                } else {
+                    Self::Palette
+                }
+            }
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Palette => "palette",
+            Self::Gradient => "gradient",
+            Self::Rise => "rise",
+            Self::Custom(_) => "custom",
+        }
+    }
+}
+
+/// A bar palette loaded from `--palettes`.
+#[derive(Clone, Debug)]
+pub struct CustomPalette {
+    pub name: String,
+    /// RGB colors from the quietest level to the loudest.
+    pub colors: Vec<[u8; 3]>,
+}
+
+/// How a projectile's hue is chosen when it spawns.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProjectileColorMode {
+    /// Cycle through the rainbow as projectiles fire.
+    #[default]
+    Rainbow,
+    /// Every projectile uses `PROJECTILE_FIXED_HUE`.
+    Fixed,
+    /// Hue follows the firing bar: bass is red, treble is violet.
+    Band,
+    /// Hue of the firing bar's color as currently drawn, so projectiles match their bar.
+    Bar,
+}
+
+impl ProjectileColorMode {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "rainbow" => Ok(Self::Rainbow),
+            "fixed" => Ok(Self::Fixed),
+            "band" => Ok(Self::Band),
+            "bar" => Ok(Self::Bar),
+            _ => Err(format!("invalid projectile colors '{}', expected rainbow, fixed, band or bar", s)),
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Self::Rainbow => Self::Fixed,
+            Self::Fixed => Self::Band,
+            Self::Band => Self::Bar,
+            Self::Bar => Self::Rainbow,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Rainbow => "rainbow",
+            Self::Fixed => "fixed",
+            Self::Band => "band",
+            Self::Bar => "bar",
+        }
+    }
+
+    /// `bar_hue` gives the hue of the firing bar's current color; only `Bar` calls it.
+    pub fn hue(self, bar_i: usize, rainbow_phase: f32, bar_hue: impl FnOnce() -> f32) -> f32 {
+        match self {
+            Self::Rainbow => rainbow_phase,
+            Self::Fixed => crate::PROJECTILE_FIXED_HUE,
+            Self::Band => bar_i as f32 / crate::BAR_COUNT as f32 * 280.0,
+            Self::Bar => bar_hue(),
+        }
+    }
+}
+
+/// How projectiles and their trails are drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProjectileShape {
+    #[default]
+    Square,
+    /// Softer round dots.
+    Circle,
+}
+
+impl ProjectileShape {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "square" => Ok(Self::Square),
+            "circle" => Ok(Self::Circle),
+            _ => Err(format!("invalid projectile shape '{}', expected square or circle", s)),
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Self::Square => Self::Circle,
+            Self::Circle => Self::Square,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Square => "square",
+            Self::Circle => "circle",
+        }
+    }
+}
+
+/// Where projectiles launch from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LaunchOrigin {
+    /// The circle the bars grow from.
+    #[default]
+    Base,
+    /// The peak of the firing bar, so projectiles fly off the bar ends.
+    Tip,
+}
+
+impl LaunchOrigin {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "base" => Ok(Self::Base),
+            "tip" => Ok(Self::Tip),
+            _ => Err(format!("invalid launch origin '{}', expected base or tip", s)),
+        }
+    }
+}
+
+/// What makes a bar fire a projectile.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FireOn {
+    /// Only a sharp jump in the bar's level.
+    Transients,
+    /// Transients anywhere, plus any new peak in a bass bar.
+    #[default]
+    TransientsAndBass,
+    /// Global beats, as bursts from the loudest bars.
+    Beats,
+}
+
+impl FireOn {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "transients" => Ok(Self::Transients),
+            "transients+bass" => Ok(Self::TransientsAndBass),
+            "beats" => Ok(Self::Beats),
+            _ => Err(format!("invalid fire-on '{}', expected transients, transients+bass or beats", s)),
+        }
+    }
+}
+
+/// How the FFT bins under a bar are reduced to its level.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BandReduce {
+    /// Root-mean-square of the bin magnitudes, i.e. the band's average power.
+    Rms,
+    /// 40% loudest bin, 60% mean magnitude.
+    #[default]
+    Mix,
+    /// Loudest bin only.
+    Max,
+}
+
+impl BandReduce {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "rms" => Ok(Self::Rms),
+            "mix" => Ok(Self::Mix),
+            "max" => Ok(Self::Max),
+            _ => Err(format!("invalid band reduction '{}', expected rms, mix or max", s)),
+        }
+    }
+}
+
+/// Corner the FPS/status overlay is drawn in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverlayAnchor {
+    #[default]
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl OverlayAnchor {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "top-left" => Ok(Self::TopLeft),
+            "top-right" => Ok(Self::TopRight),
+            "bottom-left" => Ok(Self::BottomLeft),
+            "bottom-right" => Ok(Self::BottomRight),
+            _ => Err(format!(
+                "invalid overlay corner '{}', expected top-left, top-right, bottom-left or bottom-right",
+                s
+            )),
+        }
+    }
+
+    /// Clockwise to the next corner.
+    pub fn next(self) -> Self {
+        match self {
+            Self::TopLeft => Self::TopRight,
+            Self::TopRight => Self::BottomRight,
+            Self::BottomRight => Self::BottomLeft,
+            Self::BottomLeft => Self::TopLeft,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::TopLeft => "top-left",
+            Self::TopRight => "top-right",
+            Self::BottomLeft => "bottom-left",
+            Self::BottomRight => "bottom-right",
+        }
+    }
+
+    pub fn is_right(self) -> bool {
+        matches!(self, Self::TopRight | Self::BottomRight)
+    }
+
+    pub fn is_bottom(self) -> bool {
+        matches!(self, Self::BottomLeft | Self::BottomRight)
+    }
+}
+
+/// What the picture-in-picture inset in a screen corner shows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Inset {
+    Off,
+    /// The bars laid out left to right as a linear spectrum.
+    Spectrum,
+    /// The latest analyzed chunk as an oscilloscope trace.
+    Waveform,
+}
+
+impl Inset {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "off" => Ok(Self::Off),
+            "spectrum" => Ok(Self::Spectrum),
+            "waveform" => Ok(Self::Waveform),
+            _ => Err(format!("invalid inset '{}', expected off, spectrum or waveform", s)),
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Spectrum,
+            Self::Spectrum => Self::Waveform,
+            Self::Waveform => Self::Off,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Spectrum => "spectrum",
+            Self::Waveform => "waveform",
+        }
+    }
+}
+
+/// What the optional second ring shows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SecondRing {
+    Off,
+    /// Smoothed bar heights, like the main ring.
+    Bars,
+    /// Decaying peak heights.
+    Peaks,
+}
+
+impl SecondRing {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "off" => Ok(Self::Off),
+            "bars" => Ok(Self::Bars),
+            "peaks" => Ok(Self::Peaks),
+            _ => Err(format!("invalid second ring '{}', expected off, bars or peaks", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub latency: Latency,
+    /// Kernel radius for smoothing across neighboring FFT bins; 0 = off.
+    pub spectral_smoothing: usize,
+    /// Display-only blend of each bar toward its neighbors' mean (0..1).
+    pub bar_smoothing: f32,
+    /// Treat the first and last bars as neighbors when smoothing on the full circle.
+    pub bar_smoothing_wrap: bool,
+    /// FFT frames averaged before band reduction ("RTA average"); 1 = off.
+    pub spectrum_average: usize,
+    /// Start with the averaged spectrum drawn as a contour while the bars stay live.
+    pub average_overlay: bool,
+    pub bar_colors: BarColors,
+    /// Palettes loaded from `--palettes`, cycled after the built-in bar colors.
+    pub palettes: Vec<CustomPalette>,
+    /// Scales a bar's per-chunk rise into the `BarColors::Rise` flash amount.
+    pub rise_sensitivity: f32,
+    /// Exponent applied to bar height before the palette lookup; below 1 favors hotter colors.
+    pub color_gamma: f32,
+    /// Bar envelope time constants in seconds; a short attack and longer release feel like a VU meter.
+    pub attack: f32,
+    pub release: f32,
+    /// Displayed spectrum range in Hz; bass starts at `min_freq`, highs end at `max_freq`.
+    pub min_freq: f32,
+    pub max_freq: f32,
+    pub band_reduce: BandReduce,
+    /// Bars per region (bass, mid, high); parsing rejects a split that doesn't fill the ring.
+    pub bands: [usize; 3],
+    /// Bass/mid and mid/high boundaries in Hz.
+    pub band_edges: (f32, f32),
+    pub width: u32,
+    pub height: u32,
+    pub position: Option<(u32, u32)>,
+    pub fullscreen: bool,
+    /// Frame rate cap enforced by sleeping after each frame; `None` leaves it to vsync.
+    pub max_fps: Option<f32>,
+    pub mode: RenderMode,
+    /// Inner circle radius range driven by loudness; `None` keeps it fixed.
+    pub reactive_radius: Option<(f32, f32)>,
+    /// Bars grow from the outer edge toward the center instead of outward.
+    pub bars_inward: bool,
+    /// Flash the scene brighter on spectral-flux onsets.
+    pub pulse: bool,
+    pub pulse_sensitivity: f32,
+    pub pulse_decay: f32,
+    /// How far a bar's color is pushed toward white when it registers a transient; 0 disables it.
+    pub transient_flash: f32,
+    pub overlay: OverlayAnchor,
+    pub inset: Inset,
+    pub inset_corner: OverlayAnchor,
+    /// Inset width and height as a fraction of the window's.
+    pub inset_size: f32,
+    /// Size multiplier for overlay text and panels; `None` derives it from the window height.
+    pub ui_scale: Option<f32>,
+    /// Angle (degrees, clockwise from the positive x axis) where bar 0 starts on the full circle.
+    pub start_angle: f32,
+    /// Lay the bars out counter-clockwise from `start_angle` instead of clockwise.
+    pub counter_clockwise: bool,
+    /// Starting rotation speed as steps from standstill; `None` starts without rotating.
+    pub rotate: Option<i32>,
+    /// Rotation speed follows the smoothed energy instead of the selected level.
+    pub auto_rotate: bool,
+    /// Pull `beat_align_bar` toward straight up for a moment on each beat while rotating.
+    pub beat_align: bool,
+    pub beat_align_bar: usize,
+    pub intensity: f32,
+    /// Multiplier applied to the input after level normalization.
+    pub gain: f32,
+    /// Number of LED segments each bar is split into; 0 draws continuous bars.
+    pub segments: usize,
+    /// Bars below this fraction of full height are drawn with zero length; display only.
+    pub dead_zone: f32,
+    /// Angle left empty on each side of a bar, as a fraction of the angle each bar covers.
+    pub bar_gap: f32,
+    /// How much larger a peak cap is at full height than at zero; 0 keeps every cap the same size.
+    pub peak_cap_scale: f32,
+    /// Peak-cap marker color as RGB; `None` picks the complement of each bar's color.
+    pub peak_color: Option<[u8; 3]>,
+    /// How strongly a peak cap glows while its bar's projectile cooldown runs; 0 = off.
+    pub peak_hit_glow: f32,
+    /// Inner circle color as RGB.
+    pub circle_color: [u8; 3],
+    pub circle_thickness: f32,
+    /// Derive the inner circle color from the level instead of `circle_color`.
+    pub reactive_circle: bool,
+    /// Read raw f32 PCM from stdin instead of capturing.
+    pub stdin: bool,
+    pub stdin_channels: usize,
+    pub stdin_rate: u32,
+    /// UDP port to receive PCM packets on instead of capturing.
+    pub listen: Option<u16>,
+    /// TCP port to stream audio on, headless, instead of visualizing it.
+    pub serve: Option<u16>,
+    /// `--serve` instance to receive audio from instead of capturing.
+    pub connect: Option<String>,
+    /// Capture from the default input device instead of system audio.
+    pub line_in: bool,
+    /// Play line-in audio back through the default output.
+    pub monitor: bool,
+    /// Capture through the JACK host instead of the platform default.
+    pub jack: bool,
+    /// Windows process loopback: capture only this process tree instead of the system mix.
+    pub pid: Option<u32>,
+    /// Executable name resolved to a PID for process loopback when capture starts.
+    pub process: Option<String>,
+    /// Silent chunks arriving within this many seconds of audible ones are treated as dropouts and skipped.
+    pub dropout_hold: f32,
+    /// Seconds of silence after which everything fades out; `None` keeps the last frame decaying.
+    pub silence_timeout: Option<f32>,
+    /// Feed a synthetic sine sweep instead of capturing audio.
+    pub selftest: bool,
+    /// Run this many analyzer updates headless and report throughput instead of starting.
+    pub bench_fft: Option<usize>,
+    /// Print the bar-to-frequency layout and exit.
+    pub print_bands: bool,
+    pub transient_threshold: f32,
+    pub peak_hysteresis: f32,
+    pub projectile_colors: ProjectileColorMode,
+    /// Projectiles slow down (rather than speed up) toward the edge.
+    pub projectile_decel: bool,
+    /// Slowdown follows distance to the edge rather than time alive.
+    pub projectile_distance_based: bool,
+    /// Projectile speed floor in px/s; decelerating projectiles ease onto it.
+    pub min_speed: f32,
+    pub launch_from: LaunchOrigin,
+    pub projectile_shape: ProjectileShape,
+    /// Trail opacity at the oldest and newest positions.
+    pub trail_alpha: (f32, f32),
+    /// Exponent of the tail-to-head opacity ramp; higher keeps the tail faint for longer.
+    pub trail_curve: f32,
+    /// Attract projectiles to the center so they orbit instead of flying off.
+    pub gravity_well: bool,
+    /// A4 in Hz for the note readout.
+    pub reference_pitch: f32,
+    /// Start with the loudness-reactive vignette over the scene.
+    pub vignette: bool,
+    /// Most projectiles spawned in a single frame across all bands; `None` spawns every peak.
+    pub max_spawns_per_frame: Option<usize>,
+    /// Seconds after launch when a projectile is removed wherever it is; `None` waits for it to leave the screen.
+    pub max_lifetime: Option<f32>,
+    /// What fires projectiles; `Beats` fires bursts on global onsets instead of on per-bar peaks.
+    pub fire_on: FireOn,
+    /// Projectiles per beat in beats-only mode, taken from the loudest bars.
+    pub beat_burst: usize,
+    /// Hue step (degrees) applied to the rainbow phase per beat; `None` drifts continuously.
+    pub beat_hue_step: Option<f32>,
+    pub second_ring: SecondRing,
+    pub second_ring_radius: f32,
+    /// Spin the second ring against the main ring's rotation.
+    pub second_ring_reverse: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            latency: Latency::default(),
+            spectral_smoothing: 0,
+            bar_smoothing: 0.0,
+            bar_smoothing_wrap: false,
+            spectrum_average: 1,
+            average_overlay: false,
+            bar_colors: BarColors::Palette,
+            palettes: Vec::new(),
+            rise_sensitivity: 4.0,
+            color_gamma: 1.0,
+            attack: 0.05,
+            release: 0.2,
+            min_freq: 20.0,
+            max_freq: 15000.0,
+            band_reduce: BandReduce::Mix,
+            bands: [10, 44, 10],
+            band_edges: (500.0, 3500.0),
+            width: 1024,
+            height: 768,
+            position: None,
+            fullscreen: false,
+            max_fps: None,
+            mode: RenderMode::Circle,
+            reactive_radius: None,
+            bars_inward: false,
+            pulse: false,
+            pulse_sensitivity: 1.5,
+            pulse_decay: 0.15,
+            transient_flash: 0.0,
+            overlay: OverlayAnchor::TopLeft,
+            inset: Inset::Off,
+            inset_corner: OverlayAnchor::BottomRight,
+            inset_size: 0.25,
+            ui_scale: None,
+            start_angle: -45.0,
+            counter_clockwise: false,
+            rotate: None,
+            auto_rotate: false,
+            beat_align: false,
+            beat_align_bar: 0,
+            intensity: 1.0,
+            gain: 1.0,
+            segments: 0,
+            dead_zone: 0.0,
+            bar_gap: 0.04,
+            peak_cap_scale: 0.5,
+            peak_color: Some([255, 255, 255]),
+            peak_hit_glow: 0.0,
+            circle_color: [130, 130, 130],
+            circle_thickness: 2.0,
+            reactive_circle: false,
+            stdin: false,
+            stdin_channels: 2,
+            stdin_rate: 44100,
+            listen: None,
+            serve: None,
+            connect: None,
+            line_in: false,
+            monitor: false,
+            jack: false,
+            pid: None,
+            process: None,
+            dropout_hold: 0.1,
+            silence_timeout: None,
+            selftest: false,
+            bench_fft: None,
+            print_bands: false,
+            transient_threshold: 0.025,
+            peak_hysteresis: 0.05,
+            projectile_colors: ProjectileColorMode::Rainbow,
+            projectile_decel: false,
+            projectile_distance_based: false,
+            min_speed: 90.0,
+            launch_from: LaunchOrigin::Base,
+            projectile_shape: ProjectileShape::Square,
+            trail_alpha: (0.04, 0.74),
+            trail_curve: 2.0,
+            gravity_well: false,
+            reference_pitch: 440.0,
+            vignette: false,
+            max_spawns_per_frame: None,
+            max_lifetime: None,
+            fire_on: FireOn::TransientsAndBass,
+            beat_burst: 6,
+            beat_hue_step: None,
+            second_ring: SecondRing::Off,
+            second_ring_radius: 20.0,
+            second_ring_reverse: true,
+        }
+    }
+}
+
+impl Config {
+    /// Parses the process arguments (once; later calls return the same config),
+    /// printing usage and exiting on error.
+    pub fn from_args() -> &'static Self {
+        static CONFIG: OnceLock<Config> = OnceLock::new();
+        CONFIG.get_or_init(|| match Self::parse(std::env::args().skip(1)) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("{}\n\n{}", e, USAGE);
+                process::exit(2);
+            }
+        })
+    }
+
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut config = Self::default();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+            match arg.as_str() {
+                "--latency" => config.latency = Latency::parse(&value("--latency")?)?,
+                "--bar-smoothing" => {
+                    config.bar_smoothing = parse_num(&value("--bar-smoothing")?, "--bar-smoothing")?;
+                    if !(0.0..=1.0).contains(&config.bar_smoothing) {
+                        return Err("--bar-smoothing must be between 0 and 1".into());
+                    }
+                }
+                "--bar-smoothing-wrap" => config.bar_smoothing_wrap = true,
+                "--spectral-smoothing" => {
+                    config.spectral_smoothing = parse_num(&value("--spectral-smoothing")?, "--spectral-smoothing")?;
+                    if config.spectral_smoothing > crate::MAX_SPECTRAL_SMOOTHING {
+                        return Err(format!(
+                            "--spectral-smoothing must be between 0 and {}",
+                            crate::MAX_SPECTRAL_SMOOTHING
+                        ));
+                    }
+                }
+                "--average-overlay" => config.average_overlay = true,
+                "--spectrum-average" => {
+                    config.spectrum_average = parse_num(&value("--spectrum-average")?, "--spectrum-average")?;
+                    if !(1..=64).contains(&config.spectrum_average) {
+                        return Err("--spectrum-average must be between 1 and 64".into());
+                    }
+                }
+                "--bar-colors" => config.bar_colors = BarColors::parse(&value("--bar-colors")?)?,
+                "--palettes" => {
+                    let path = value("--palettes")?;
+                    let text = std::fs::read_to_string(&path)
+                        .map_err(|e| format!("can't read --palettes file '{}': {}", path, e))?;
+                    config.palettes = parse_palettes(&text, &path);
+                }
+                "--rise-sensitivity" => {
+                    config.rise_sensitivity =
+                        parse_finite(&value("--rise-sensitivity")?, "--rise-sensitivity", |s| s >= 0.0, "not be negative")?
+                }
+                "--color-gamma" => {
+                    config.color_gamma = parse_finite(&value("--color-gamma")?, "--color-gamma", |g| g > 0.0, "be greater than 0")?
+                }
+                "--attack" => {
+                    config.attack = parse_finite(&value("--attack")?, "--attack", |s| s >= 0.0, "be 0 or more seconds")?
+                }
+                "--release" => {
+                    config.release = parse_finite(&value("--release")?, "--release", |s| s >= 0.0, "be 0 or more seconds")?
+                }
+                "--min-freq" => config.min_freq = parse_num(&value("--min-freq")?, "--min-freq")?,
+                "--max-freq" => config.max_freq = parse_num(&value("--max-freq")?, "--max-freq")?,
+                "--band-reduce" => config.band_reduce = BandReduce::parse(&value("--band-reduce")?)?,
+                "--bands" => {
+                    let v = value("--bands")?;
+                    let parts: Vec<&str> = v.split(',').collect();
+                    let [bass, mid, high] = parts[..] else {
+                        return Err(format!("invalid bands '{}', expected BASS,MID,HIGH", v));
+                    };
+                    config.bands = [
+                        parse_num(bass, "--bands")?,
+                        parse_num(mid, "--bands")?,
+                        parse_num(high, "--bands")?,
+                    ];
+                    if config.bands.iter().sum::<usize>() != crate::BAR_COUNT || config.bands.contains(&0) {
+                        return Err(format!("--bands must be non-zero and add up to {}", crate::BAR_COUNT));
+                    }
+                }
+                "--band-edges" => {
+                    let v = value("--band-edges")?;
+                    let (lo, hi) = v
+                        .split_once(',')
+                        .ok_or_else(|| format!("invalid band edges '{}', expected LO,HI", v))?;
+                    config.band_edges = (parse_num(lo, "--band-edges")?, parse_num(hi, "--band-edges")?);
+                }
+                "--width" => config.width = parse_num(&value("--width")?, "--width")?,
+                "--height" => config.height = parse_num(&value("--height")?, "--height")?,
+                "--position" => {
+                    let v = value("--position")?;
+                    let (x, y) = v
+                        .split_once(',')
+                        .ok_or_else(|| format!("invalid position '{}', expected X,Y", v))?;
+                    config.position = Some((parse_num(x, "--position")?, parse_num(y, "--position")?));
+                }
+                "--fullscreen" => config.fullscreen = true,
+                "--max-fps" => {
+                    let fps = parse_finite(&value("--max-fps")?, "--max-fps", |fps| fps >= 1.0, "be at least 1")?;
+                    config.max_fps = Some(fps);
+                }
+                "--mode" => config.mode = RenderMode::parse(&value("--mode")?)?,
+                "--reactive-radius" => {
+                    let v = value("--reactive-radius")?;
+                    let (lo, hi) = v
+                        .split_once(',')
+                        .ok_or_else(|| format!("invalid range '{}', expected MIN,MAX", v))?;
+                    let (lo, hi): (f32, f32) =
+                        (parse_num(lo, "--reactive-radius")?, parse_num(hi, "--reactive-radius")?);
+                    if !(0.0..=hi).contains(&lo) {
+                        return Err("--reactive-radius needs 0 <= MIN <= MAX".into());
+                    }
+                    config.reactive_radius = Some((lo, hi));
+                }
+                "--inward" => config.bars_inward = true,
+                "--pulse" => config.pulse = true,
+                "--pulse-sensitivity" => {
+                    config.pulse_sensitivity =
+                        parse_finite(&value("--pulse-sensitivity")?, "--pulse-sensitivity", |s| s > 0.0, "be greater than 0")?
+                }
+                "--pulse-decay" => {
+                    config.pulse_decay = parse_finite(&value("--pulse-decay")?, "--pulse-decay", |s| s > 0.0, "be greater than 0")?
+                }
+                "--transient-flash" => {
+                    config.transient_flash = parse_num(&value("--transient-flash")?, "--transient-flash")?;
+                    if !(0.0..=1.0).contains(&config.transient_flash) {
+                        return Err("--transient-flash must be between 0 and 1".into());
+                    }
+                }
+                "--transient-threshold" => {
+                    config.transient_threshold =
+                        parse_finite(&value("--transient-threshold")?, "--transient-threshold", |t| t >= 0.0, "not be negative")?
+                }
+                "--peak-hysteresis" => {
+                    config.peak_hysteresis =
+                        parse_finite(&value("--peak-hysteresis")?, "--peak-hysteresis", |h| h >= 0.0, "not be negative")?
+                }
+                "--projectile-colors" => {
+                    config.projectile_colors = ProjectileColorMode::parse(&value("--projectile-colors")?)?
+                }
+                "--projectile-motion" => {
+                    config.projectile_decel = match value("--projectile-motion")?.as_str() {
+                        "accel" => false,
+                        "decel" => true,
+                        other => return Err(format!("invalid projectile motion '{}', expected accel or decel", other)),
+                    }
+                }
+                "--projectile-slowdown" => {
+                    config.projectile_distance_based = match value("--projectile-slowdown")?.as_str() {
+                        "time" => false,
+                        "distance" => true,
+                        other => return Err(format!("invalid slowdown '{}', expected time or distance", other)),
+                    }
+                }
+                "--projectile-shape" => {
+                    config.projectile_shape = ProjectileShape::parse(&value("--projectile-shape")?)?
+                }
+                "--min-speed" => {
+                    // Slower projectiles barely move, so in decel mode they'd pile up on screen
+                    config.min_speed = parse_finite(&value("--min-speed")?, "--min-speed", |s| s >= 10.0, "be at least 10")?;
+                }
+                "--launch-from" => config.launch_from = LaunchOrigin::parse(&value("--launch-from")?)?,
+                "--trail-alpha" => {
+                    let v = value("--trail-alpha")?;
+                    let (lo, hi) = v
+                        .split_once(',')
+                        .ok_or_else(|| format!("invalid range '{}', expected MIN,MAX", v))?;
+                    let (lo, hi): (f32, f32) = (parse_num(lo, "--trail-alpha")?, parse_num(hi, "--trail-alpha")?);
+                    if !(0.0..=1.0).contains(&lo) || !(0.0..=1.0).contains(&hi) {
+                        return Err("--trail-alpha values must be between 0 and 1".into());
+                    }
+                    config.trail_alpha = (lo, hi);
+                }
+                "--trail-curve" => {
+                    config.trail_curve = parse_finite(&value("--trail-curve")?, "--trail-curve", |c| c > 0.0, "be greater than 0")?
+                }
+                "--gravity-well" => config.gravity_well = true,
+                "--reference-pitch" => {
+                    config.reference_pitch = parse_num(&value("--reference-pitch")?, "--reference-pitch")?;
+                    if !(400.0..=480.0).contains(&config.reference_pitch) {
+                        return Err("--reference-pitch must be between 400 and 480".into());
+                    }
+                }
+                "--vignette" => config.vignette = true,
+                "--max-spawns" => {
+                    let spawns: usize = parse_num(&value("--max-spawns")?, "--max-spawns")?;
+                    if spawns == 0 {
+                        return Err("--max-spawns must be at least 1".into());
+                    }
+                    config.max_spawns_per_frame = Some(spawns);
+                }
+                "--max-lifetime" => {
+                    let secs = parse_finite(&value("--max-lifetime")?, "--max-lifetime", |s| s > 0.0, "be greater than 0")?;
+                    config.max_lifetime = Some(secs);
+                }
+                "--fire-on" => config.fire_on = FireOn::parse(&value("--fire-on")?)?,
+                "--beats-only" => config.fire_on = FireOn::Beats,
+                "--beat-burst" => {
+                    config.beat_burst = parse_num(&value("--beat-burst")?, "--beat-burst")?;
+                    if config.beat_burst == 0 {
+                        return Err("--beat-burst must be at least 1".into());
+                    }
+                }
+                "--beat-hue-step" => {
+                    let step = parse_finite(&value("--beat-hue-step")?, "--beat-hue-step", |_| true, "")?;
+                    config.beat_hue_step = Some(step.rem_euclid(360.0));
+                }
+                "--second-ring" => config.second_ring = SecondRing::parse(&value("--second-ring")?)?,
+                "--second-ring-radius" => {
+                    config.second_ring_radius =
+                        parse_finite(&value("--second-ring-radius")?, "--second-ring-radius", |r| r >= 0.0, "not be negative")?
+                }
+                "--second-ring-direction" => {
+                    config.second_ring_reverse = match value("--second-ring-direction")?.as_str() {
+                        "same" => false,
+                        "reverse" => true,
+                        other => return Err(format!("invalid direction '{}', expected same or reverse", other)),
+                    }
+                }
+                "--start-angle" => {
+                    config.start_angle = parse_finite(&value("--start-angle")?, "--start-angle", |_| true, "")?
+                }
+                "--winding" => {
+                    config.counter_clockwise = match value("--winding")?.as_str() {
+                        "cw" => false,
+                        "ccw" => true,
+                        other => return Err(format!("invalid winding '{}', expected cw or ccw", other)),
+                    }
+                }
+                "--rotate" => {
+                    let level: i32 = parse_num(&value("--rotate")?, "--rotate")?;
+                    let max = (crate::SPEED_LEVELS.len() / 2) as i32;
+                    if level.abs() > max {
+                        return Err(format!("--rotate must be between -{} and {}", max, max));
+                    }
+                    config.rotate = Some(level);
+                }
+                "--auto-rotate" => config.auto_rotate = true,
+                "--beat-align" => config.beat_align = true,
+                "--beat-align-bar" => {
+                    config.beat_align_bar = parse_num(&value("--beat-align-bar")?, "--beat-align-bar")?;
+                    if config.beat_align_bar >= crate::BAR_COUNT {
+                        return Err(format!("--beat-align-bar must be below {}", crate::BAR_COUNT));
+                    }
+                }
+                "--intensity" => {
+                    config.intensity = parse_num(&value("--intensity")?, "--intensity")?;
+                    if !(crate::MIN_INTENSITY..=crate::MAX_INTENSITY).contains(&config.intensity) {
+                        return Err(format!(
+                            "--intensity must be between {} and {}",
+                            crate::MIN_INTENSITY,
+                            crate::MAX_INTENSITY
+                        ));
+                    }
+                }
+                "--gain" => config.gain = parse_finite(&value("--gain")?, "--gain", |g| g > 0.0, "be greater than 0")?,
+                "--overlay" => config.overlay = OverlayAnchor::parse(&value("--overlay")?)?,
+                "--inset" => config.inset = Inset::parse(&value("--inset")?)?,
+                "--inset-corner" => config.inset_corner = OverlayAnchor::parse(&value("--inset-corner")?)?,
+                "--inset-size" => {
+                    config.inset_size = parse_num(&value("--inset-size")?, "--inset-size")?;
+                    if !(0.1..=0.5).contains(&config.inset_size) {
+                        return Err("--inset-size must be between 0.1 and 0.5".into());
+                    }
+                }
+                "--ui-scale" => {
+                    let v = value("--ui-scale")?;
+                    config.ui_scale = if v == "auto" {
+                        None
+                    } else {
+                        let scale: f32 = parse_num(&v, "--ui-scale")?;
+                        if !(0.5..=4.0).contains(&scale) {
+                            return Err("--ui-scale must be between 0.5 and 4, or auto".into());
+                        }
+                        Some(scale)
+                    };
+                }
+                "--segments" => {
+                    config.segments = parse_num(&value("--segments")?, "--segments")?;
+                    if config.segments > 64 {
+                        return Err("--segments must be at most 64".into());
+                    }
+                }
+                "--bar-gap" => {
+                    config.bar_gap = parse_num(&value("--bar-gap")?, "--bar-gap")?;
+                    // Half a slot on each side would leave nothing, and more would invert the bar
+                    if !(0.0..=0.45).contains(&config.bar_gap) {
+                        return Err("--bar-gap must be between 0 and 0.45".into());
+                    }
+                }
+                "--dead-zone" => {
+                    config.dead_zone = parse_num(&value("--dead-zone")?, "--dead-zone")?;
+                    if !(0.0..=0.2).contains(&config.dead_zone) {
+                        return Err("--dead-zone must be between 0 and 0.2".into());
+                    }
+                }
+                "--peak-cap-scale" => {
+                    config.peak_cap_scale =
+                        parse_finite(&value("--peak-cap-scale")?, "--peak-cap-scale", |s| s >= 0.0, "not be negative")?
+                }
+                "--peak-color" => {
+                    let v = value("--peak-color")?;
+                    config.peak_color = if v == "auto" {
+                        None
+                    } else {
+                        Some(parse_color(&v, "--peak-color")?)
+                    };
+                }
+                "--peak-hit-glow" => {
+                    config.peak_hit_glow = parse_num(&value("--peak-hit-glow")?, "--peak-hit-glow")?;
+                    if !(0.0..=1.0).contains(&config.peak_hit_glow) {
+                        return Err("--peak-hit-glow must be between 0 and 1".into());
+                    }
+                }
+                "--circle-color" => config.circle_color = parse_color(&value("--circle-color")?, "--circle-color")?,
+                "--circle-thickness" => {
+                    config.circle_thickness =
+                        parse_finite(&value("--circle-thickness")?, "--circle-thickness", |t| t > 0.0, "be greater than 0")?
+                }
+                "--reactive-circle" => config.reactive_circle = true,
+                "--input" => match value("--input")?.as_str() {
+                    "-" => config.stdin = true,
+                    other => return Err(format!("unsupported input '{}', only - (stdin) is supported", other)),
+                },
+                "--channels" => {
+                    config.stdin_channels = parse_num(&value("--channels")?, "--channels")?;
+                    if config.stdin_channels == 0 {
+                        return Err("--channels must be at least 1".into());
+                    }
+                }
+                "--serve" => config.serve = Some(parse_num(&value("--serve")?, "--serve")?),
+                "--connect" => config.connect = Some(value("--connect")?),
+                "--listen" => config.listen = Some(parse_num(&value("--listen")?, "--listen")?),
+                "--rate" => {
+                    config.stdin_rate = parse_num(&value("--rate")?, "--rate")?;
+                    if config.stdin_rate == 0 {
+                        return Err("--rate must be greater than 0".into());
+                    }
+                }
+                "--line-in" => config.line_in = true,
+                "--monitor" => config.monitor = true,
+                "--jack" => {
+                    // cpal only has a JACK host on Linux and the BSDs
+                    if !cfg!(all(
+                        feature = "jack",
+                        any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd", target_os = "netbsd")
+                    )) {
+                        return Err(
+                            "--jack needs a Linux or BSD build with the `jack` feature (cargo build --features jack)".into(),
+                        );
+                    }
+                    config.jack = true;
+                }
+                "--pid" => {
+                    if !cfg!(windows) {
+                        return Err("--pid is only supported on Windows".into());
+                    }
+                    config.pid = Some(parse_num(&value("--pid")?, "--pid")?);
+                }
+                "--process" => {
+                    if !cfg!(windows) {
+                        return Err("--process is only supported on Windows".into());
+                    }
+                    config.process = Some(value("--process")?);
+                }
+                "--dropout-hold" => {
+                    config.dropout_hold =
+                        parse_finite(&value("--dropout-hold")?, "--dropout-hold", |s| s >= 0.0, "be 0 or more seconds")?
+                }
+                "--silence-timeout" => {
+                    let seconds =
+                        parse_finite(&value("--silence-timeout")?, "--silence-timeout", |s| s >= 0.0, "be 0 or more seconds")?;
+                    config.silence_timeout = Some(seconds);
+                }
+                "--selftest" => config.selftest = true,
+                "--bench-fft" => {
+                    let updates: usize = parse_num(&value("--bench-fft")?, "--bench-fft")?;
+                    if updates == 0 {
+                        return Err("--bench-fft must be greater than 0".into());
+                    }
+                    config.bench_fft = Some(updates);
+                }
+                "--print-bands" => config.print_bands = true,
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    process::exit(0);
+                }
+                _ => return Err(format!("unknown option '{}'", arg)),
+            }
+        }
+        // The bass region ends at the first band edge and the high region starts at the second
+        let nyquist = crate::SAMPLE_RATE as f32 / 2.0;
+        let (bass_mid, mid_high) = config.band_edges;
+        if !(bass_mid > 0.0 && bass_mid < mid_high) {
+            return Err("--band-edges must be above 0 with LO below HI".into());
+        }
+        if !(config.min_freq > 0.0 && config.min_freq < bass_mid) {
+            return Err(format!("--min-freq must be above 0 and below the bass/mid edge ({} Hz)", bass_mid));
+        }
+        if !(config.max_freq > mid_high && config.max_freq <= nyquist) {
+            return Err(format!(
+                "--max-freq must be above the mid/high edge ({} Hz) and at most Nyquist ({} Hz)",
+                mid_high, nyquist
+            ));
+        }
+        // Monitoring a loopback source would play system audio back into itself
+        if config.monitor && !(config.line_in || config.jack) {
+            return Err("--monitor needs --line-in or --jack".into());
+        }
+        if config.pid.is_some() && (config.line_in || config.jack) {
+            return Err("--pid selects a loopback source and can't be combined with --line-in or --jack".into());
+        }
+        if config.process.is_some() && (config.pid.is_some() || config.line_in || config.jack) {
+            return Err("--process can't be combined with --pid, --line-in or --jack".into());
+        }
+        // Each of these replaces local capture, so only one can be used and capture options don't apply
+        let sources: Vec<&str> = [
+            (config.selftest, "--selftest"),
+            (config.stdin, "--input -"),
+            (config.listen.is_some(), "--listen"),
+            (config.connect.is_some(), "--connect"),
+        ]
+        .into_iter()
+        .filter_map(|(set, name)| set.then_some(name))
+        .collect();
+        if sources.len() > 1 {
+            return Err(format!("{} can't be combined, pick one audio source", sources.join(" and ")));
+        }
+        if let Some(source) = sources.first() {
+            let capture_options = [
+                (config.line_in, "--line-in"),
+                (config.jack, "--jack"),
+                (config.pid.is_some(), "--pid"),
+                (config.process.is_some(), "--process"),
+                (config.monitor, "--monitor"),
+            ];
+            if let Some((_, option)) = capture_options.iter().find(|(set, _)| *set) {
+                return Err(format!("{} only applies to local capture and can't be combined with {}", option, source));
+            }
+        }
+        if config.width == 0 || config.height == 0 {
+            return Err("--width and --height must be greater than 0".into());
+        }
+        Ok(config)
+    }
+}
+
+fn parse_num<T: std::str::FromStr>(s: &str, name: &str) -> Result<T, String> {
+    s.parse().map_err(|_| format!("invalid value '{}' for {}", s, name))
+}
+
+/// Parses a finite number for `name` that passes `valid`; NaN and infinities are always
+/// rejected. `rule` completes the range error, e.g. "be greater than 0".
+fn parse_finite(s: &str, name: &str, valid: impl FnOnce(f32) -> bool, rule: &str) -> Result<f32, String> {
+    let x: f32 = parse_num(s, name)?;
+    if !x.is_finite() {
+        return Err(format!("{} must be a finite number", name));
+    }
+    if !valid(x) {
+        return Err(format!("{} must {}", name, rule));
+    }
+    Ok(x)
+}
+
+/// Parses a palettes file: one `name = ["#33cc33", "#ffcc00", ...]` per line, lowest level
+/// first, with `#` comments and `[section]` headers ignored. Malformed entries are skipped
+/// with a warning.
+fn parse_palettes(text: &str, path: &str) -> Vec<CustomPalette> {
+    let mut palettes = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('[') {
+            continue;
+        }
+        let warn = |why: &str| eprintln!("{}:{}: skipping palette, {}", path, n + 1, why);
+        let Some((name, list)) = line.split_once('=') else {
+            warn("expected name = [colors]");
+            continue;
+        };
+        let name = name.trim().trim_matches('"');
+        if name.is_empty() {
+            warn("missing name");
+            continue;
+        }
+        let Some(list) = list.trim().strip_prefix('[').and_then(|l| l.strip_suffix(']')) else {
+            warn("colors must be a [ ... ] list on one line");
+            continue;
+        };
+        let colors: Result<Vec<[u8; 3]>, String> = list
+            .split(',')
+            .map(|c| c.trim().trim_matches(|q| q == '"' || q == '\''))
+            .filter(|c| !c.is_empty())
+            .map(|c| parse_color(c, name))
+            .collect();
+        match colors {
+            Ok(colors) if colors.is_empty() => warn("no colors"),
+            Ok(colors) => palettes.push(CustomPalette {
+                name: name.to_string(),
+                colors,
+            }),
+            Err(e) => warn(&e),
+        }
+    }
+    palettes
+}
+
+/// Parses a hex color such as `ff8800` or `#ff8800`.
+fn parse_color(s: &str, name: &str) -> Result<[u8; 3], String> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    let invalid = || format!("invalid color '{}' for {}, expected RRGGBB", s, name);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return Err(invalid());
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+    Ok([channel(0)?, channel(2)?, channel(4)?])
+}
@@ -5,26 +5,32 @@
 #[cfg(windows)]
 mod capture_windows;
 
+mod config;
+
 #[cfg(target_os = "macos")]
 mod capture_macos_sck;
 
+use config::{
+    BandReduce, BarColors, Config, FireOn, Inset, Latency, LaunchOrigin, OverlayAnchor, ProjectileShape, RenderMode,
+    SecondRing,
+};
 use macroquad::prelude::*;
-#[cfg(not(windows))]
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-#[cfg(not(windows))]
-use cpal::{FromSample, Sample};
+use cpal::Sample;
 use rustfft::num_complex::Complex;
 use rustfft::{Fft, FftPlanner};
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use std::sync::mpsc;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread;
-#[cfg(not(windows))]
 use std::time::Duration;
 
 // Configuration
 const FFT_SIZE: usize = 2048;
 const BAR_COUNT: usize = 64;
+/// Rate the band layout assumes; backends report what they actually deliver.
+const SAMPLE_RATE: u32 = 44100;
 
 /// Discrete speed levels: ... ccw, 0 (neutral), cw ... Tap Up/Down to step.
 /// 21 levels: -90° to +90° in 9° increments
@@ -84,6 +90,106 @@ fn hsv_to_color(h: f32, s: f32, v: f32) -> Color {
     Color::new(r + m, g + m, b + m, 1.0)
 }
 
+/// Hue of a color in degrees (0..360); grays come out as 0 (red).
+fn color_hue(c: Color) -> f32 {
+    let max = c.r.max(c.g).max(c.b);
+    let delta = max - c.r.min(c.g).min(c.b);
+    if delta <= 0.0 {
+        return 0.0;
+    }
+    let h = if max == c.r {
+        (c.g - c.b) / delta
+    } else if max == c.g {
+        (c.b - c.r) / delta + 2.0
+    } else {
+        (c.r - c.g) / delta + 4.0
+    };
+    (h * 60.0).rem_euclid(360.0)
+}
+
+/// Complementary hue of `c` for `--peak-color auto`, so caps stand out from their bar;
+/// gray bars have no hue to oppose and get white caps.
+fn contrast_color(c: Color) -> Color {
+    let max = c.r.max(c.g).max(c.b);
+    if max - c.r.min(c.g).min(c.b) < 0.1 {
+        return WHITE;
+    }
+    hsv_to_color(color_hue(c) + 180.0, 0.6, 1.0)
+}
+
+/// Time constant (s) for the applied rotation speed to follow the selected level; settles in ~0.2 s.
+const ROTATION_EASE: f32 = 0.07;
+/// Time constant (s) of the energy average that drives auto-rotate, so speed follows
+/// passages rather than single hits.
+const AUTO_ROTATE_SMOOTHING: f32 = 1.5;
+/// How long after a beat the reference bar is pulled toward the top, in seconds.
+const BEAT_ALIGN_TIME: f32 = 0.12;
+/// Time constant of that pull; with `BEAT_ALIGN_TIME` it closes roughly two thirds of the gap.
+const BEAT_ALIGN_EASE: f32 = 0.1;
+
+/// Bottom of the dB display scale: heights at or below this many dB under full scale sit on the inner circle.
+const DB_RANGE: f32 = 60.0;
+/// Reference rings drawn by the dB grid.
+const DB_GRID_LEVELS: [f32; 4] = [-40.0, -20.0, -10.0, 0.0];
+
+/// Maps a linear bar height (1.0 = full scale) to a fraction of the bar length, optionally on a dB scale.
+fn height_to_fraction(height: f32, db_scale: bool) -> f32 {
+    if !db_scale {
+        return height;
+    }
+    let db = 20.0 * height.max(1e-6).log10();
+    ((db + DB_RANGE) / DB_RANGE).clamp(0.0, 1.0)
+}
+
+/// Largest kernel radius the spectral smoothing key cycles through.
+const MAX_SPECTRAL_SMOOTHING: usize = 3;
+/// Largest display dead zone and its key step, as fractions of full bar height.
+const MAX_DEAD_ZONE: f32 = 0.2;
+const DEAD_ZONE_STEP: f32 = 0.01;
+/// Largest bar gap and its key step, as fractions of a bar's slot; below half so bars never invert.
+const MAX_BAR_GAP: f32 = 0.45;
+const BAR_GAP_STEP: f32 = 0.01;
+
+/// Convolves `input` with a normalized triangular kernel of the given radius
+/// (radius 1 = [0.25, 0.5, 0.25]); weights are renormalized at the edges.
+fn smooth_spectrum(input: &[f32], output: &mut [f32], radius: usize) {
+    let n = input.len();
+    for (i, out) in output.iter_mut().enumerate().take(n) {
+        let lo = i.saturating_sub(radius);
+        let hi = (i + radius).min(n - 1);
+        let mut sum = 0.0;
+        let mut weight_sum = 0.0;
+        for (j, &v) in input.iter().enumerate().take(hi + 1).skip(lo) {
+            let w = (radius + 1 - i.abs_diff(j)) as f32;
+            sum += v * w;
+            weight_sum += w;
+        }
+        *out = sum / weight_sum;
+    }
+}
+
+/// Blends each bar toward the mean of its two neighbors by `strength` (0..1), a 3-tap kernel
+/// for the display. The first and last bars have one neighbor each unless `wrap` joins them,
+/// which only makes sense where they meet on the full circle.
+fn smooth_bars(heights: &[f32; BAR_COUNT], strength: f32, wrap: bool) -> [f32; BAR_COUNT] {
+    let mut out = *heights;
+    if strength <= 0.0 {
+        return out;
+    }
+    let last = BAR_COUNT - 1;
+    for (i, out) in out.iter_mut().enumerate() {
+        let prev = if i > 0 { Some(heights[i - 1]) } else { wrap.then_some(heights[last]) };
+        let next = if i < last { Some(heights[i + 1]) } else { wrap.then_some(heights[0]) };
+        let neighbors = match (prev, next) {
+            (Some(a), Some(b)) => (a + b) * 0.5,
+            (Some(a), None) | (None, Some(a)) => a,
+            (None, None) => heights[i],
+        };
+        *out = heights[i] + (neighbors - heights[i]) * strength;
+    }
+    out
+}
+
 // Color palette (Windows classic vibe)
 const COLORS: [Color; 8] = [
     Color::new(0.2, 0.8, 0.2, 1.0),  // Green
@@ -96,6 +202,655 @@ const COLORS: [Color; 8] = [
     Color::new(0.8, 0.1, 0.5, 1.0),  // Purple
 ];
 
+/// Hue used by `ProjectileColorMode::Fixed`.
+const PROJECTILE_FIXED_HUE: f32 = 190.0;
+
+/// How far the onset pulse pushes colors toward white at full strength.
+const PULSE_BRIGHTNESS: f32 = 0.35;
+
+/// Time constant (s) of a bar's transient flash; gone after a handful of frames.
+const TRANSIENT_FLASH_DECAY: f32 = 0.08;
+
+/// Frames a bar waits after firing before it can fire again; also times the peak-cap hit glow,
+/// so both last longer in wall time at lower frame rates.
+const FIRE_COOLDOWN: u8 = 8;
+
+/// Draws one projectile head or trail dot of width `size` centered on (x, y).
+fn draw_dot(shape: ProjectileShape, x: f32, y: f32, size: f32, color: Color) {
+    let half = size / 2.0;
+    match shape {
+        ProjectileShape::Square => draw_rectangle(x - half, y - half, size, size, color),
+        ProjectileShape::Circle => draw_circle(x, y, half, color),
+    }
+}
+
+/// Blends a color toward white by `amount` (0..1), keeping its alpha.
+fn brighten(c: Color, amount: f32) -> Color {
+    let amount = amount.clamp(0.0, 1.0);
+    Color::new(
+        c.r + (1.0 - c.r) * amount,
+        c.g + (1.0 - c.g) * amount,
+        c.b + (1.0 - c.b) * amount,
+        c.a,
+    )
+}
+
+/// Palette color for a bar of the given height. `gamma` < 1 pushes bars into the hotter colors sooner.
+fn palette_color(palette: &[Color], height: f32, gamma: f32) -> Color {
+    let color_index = (height.max(0.0).powf(gamma) * (palette.len() - 1) as f32) as usize;
+    palette[color_index.min(palette.len() - 1)]
+}
+
+/// Hue at zero height for `BarColors::Gradient`; full height reaches 0° (red).
+const GRADIENT_LOW_HUE: f32 = 240.0;
+
+/// Color `BarColors::Rise` pushes fast-rising bars toward.
+const RISE_HOT_COLOR: Color = Color::new(1.0, 0.85, 0.4, 1.0);
+
+/// Bar color for the given height under the selected coloring. `rise` (0..1) is how
+/// fast the bar is climbing and only matters for `BarColors::Rise`. `palettes` holds the
+/// colors of the `--palettes` entries that `BarColors::Custom` indexes.
+fn bar_color(mode: BarColors, palettes: &[Vec<Color>], height: f32, rise: f32, gamma: f32) -> Color {
+    match mode {
+        BarColors::Palette => palette_color(&COLORS, height, gamma),
+        BarColors::Custom(i) => palette_color(&palettes[i], height, gamma),
+        BarColors::Gradient => {
+            let t = height.clamp(0.0, 1.0).powf(gamma);
+            hsv_to_color(GRADIENT_LOW_HUE * (1.0 - t), 0.85, 0.9)
+        }
+        BarColors::Rise => {
+            let c = palette_color(&COLORS, height, gamma);
+            let t = rise.clamp(0.0, 1.0);
+            Color::new(
+                c.r + (RISE_HOT_COLOR.r - c.r) * t,
+                c.g + (RISE_HOT_COLOR.g - c.g) * t,
+                c.b + (RISE_HOT_COLOR.b - c.b) * t,
+                c.a,
+            )
+        }
+    }
+}
+
+/// Fills the ring segment between two radii and two angles.
+fn draw_wedge(cx: f32, cy: f32, r_inner: f32, r_outer: f32, start_angle: f32, end_angle: f32, color: Color) {
+    let v1 = Vec2::new(cx + r_inner * start_angle.cos(), cy + r_inner * start_angle.sin());
+    let v2 = Vec2::new(cx + r_outer * start_angle.cos(), cy + r_outer * start_angle.sin());
+    let v3 = Vec2::new(cx + r_outer * end_angle.cos(), cy + r_outer * end_angle.sin());
+    let v4 = Vec2::new(cx + r_inner * end_angle.cos(), cy + r_inner * end_angle.sin());
+    draw_triangle(v1, v2, v3, color);
+    draw_triangle(v1, v3, v4, color);
+}
+
+/// Where the bar ring sits this frame, so it can be drawn into any part of the window.
+#[derive(Clone, Copy, Debug)]
+struct RingGeometry {
+    center: Vec2,
+    /// Radius the bars grow from: the inner circle, or the outer edge for inward bars.
+    anchor_radius: f32,
+    /// Length of a full-height bar.
+    max_bar_length: f32,
+    /// 1 when bars grow outward, -1 inward.
+    bar_dir: f32,
+    /// Angle of bar 0's leading edge and the signed angle each bar covers.
+    arc_start: f32,
+    angle_step: f32,
+    /// Angle trimmed from both sides of every bar.
+    gap: f32,
+}
+
+impl RingGeometry {
+    /// Start and end angle of bar `i` starting from `arc_start`, gaps trimmed.
+    fn bar_angles(&self, arc_start: f32, i: usize) -> (f32, f32) {
+        (
+            arc_start + i as f32 * self.angle_step + self.gap,
+            arc_start + (i + 1) as f32 * self.angle_step - self.gap,
+        )
+    }
+
+    /// Radius reached by a bar of `length`, clamped at the center for inward bars.
+    fn tip_radius(&self, length: f32) -> f32 {
+        (self.anchor_radius + self.bar_dir * length).max(0.0)
+    }
+
+    /// Fills the part of the ring bar `i` covers between two radii.
+    fn wedge(&self, arc_start: f32, i: usize, r_inner: f32, r_outer: f32, color: Color) {
+        let (start_angle, end_angle) = self.bar_angles(arc_start, i);
+        draw_wedge(self.center.x, self.center.y, r_inner, r_outer, start_angle, end_angle, color);
+    }
+}
+
+/// How bars are colored and scaled this frame.
+struct BarTheme<'a> {
+    bar_colors: BarColors,
+    palettes: &'a [Vec<Color>],
+    color_gamma: f32,
+    db_scale: bool,
+    /// Heights below this are drawn empty.
+    dead_zone: f32,
+    /// Onset pulse brightening everything.
+    pulse: f32,
+    /// Peak-cap color; `None` contrasts with each bar.
+    peak_color: Option<Color>,
+}
+
+/// Draws the main bars with their peak caps, or LED segments with `--segments`.
+fn draw_bar_ring(
+    ring: &RingGeometry,
+    heights: &[f32; BAR_COUNT],
+    state: &VisualizerState,
+    theme: &BarTheme,
+    config: &Config,
+) {
+    let (cx, cy) = (ring.center.x, ring.center.y);
+    for (i, &height) in heights.iter().enumerate() {
+        let shown = if height < theme.dead_zone { 0.0 } else { height };
+        let bar_length = height_to_fraction(shown, theme.db_scale) * ring.max_bar_length;
+        let (start_angle, _) = ring.bar_angles(ring.arc_start, i);
+
+        let rise = state.bar_deltas[i] * config.rise_sensitivity;
+        let flash = state.bar_flash[i] * config.transient_flash;
+        if config.segments > 0 {
+            // LED meter: segments up to the bar's level are lit green to red by position,
+            // the rest stay dim, and the peak lights its own segment above them
+            let n = config.segments;
+            let seg_length = ring.max_bar_length / n as f32;
+            let lit = (height_to_fraction(shown, theme.db_scale) * n as f32).round() as usize;
+            let peak_segment = (height_to_fraction(state.peak_heights[i], theme.db_scale) * n as f32).ceil() as usize;
+            for k in 0..n {
+                let inner = ring.tip_radius(k as f32 * seg_length);
+                let outer = ring.tip_radius(((k + 1) as f32 - LED_SEGMENT_GAP) * seg_length);
+                let color = if k < lit {
+                    let t = (k as f32 + 0.5) / n as f32;
+                    brighten(hsv_to_color(LED_LOW_HUE * (1.0 - t), 0.9, 0.95), theme.pulse + flash)
+                } else if k + 1 == peak_segment {
+                    let t = (k as f32 + 0.5) / n as f32;
+                    theme
+                        .peak_color
+                        .unwrap_or_else(|| contrast_color(hsv_to_color(LED_LOW_HUE * (1.0 - t), 0.9, 0.95)))
+                } else {
+                    LED_UNLIT
+                };
+                ring.wedge(ring.arc_start, i, inner, outer, color);
+            }
+            continue;
+        }
+        let color = brighten(
+            bar_color(theme.bar_colors, theme.palettes, height, rise, theme.color_gamma),
+            theme.pulse + flash,
+        );
+        ring.wedge(ring.arc_start, i, ring.anchor_radius, ring.tip_radius(bar_length), color);
+
+        let peak_length = height_to_fraction(state.peak_heights[i], theme.db_scale) * ring.max_bar_length;
+        if peak_length > 3.0 {
+            let peak_radius = ring.tip_radius(peak_length);
+            let mid_angle = start_angle + ring.angle_step * 0.5;
+            let px = cx + peak_radius * mid_angle.cos();
+            let py = cy + peak_radius * mid_angle.sin();
+            // Louder peaks get longer, thicker caps; a bar that just fired glows briefly
+            let glow = config.peak_hit_glow * state.fire_cooldown[i] as f32 / FIRE_COOLDOWN as f32;
+            let weight = (1.0 + config.peak_cap_scale * state.peak_heights[i].clamp(0.0, 1.0)) * (1.0 + glow);
+            let cap_color = brighten(theme.peak_color.unwrap_or_else(|| contrast_color(color)), glow);
+            let perp_x = -mid_angle.sin() * 4.0 * weight;
+            let perp_y = mid_angle.cos() * 4.0 * weight;
+            draw_line(px - perp_x, py - perp_y, px + perp_x, py + perp_y, 2.0 * weight, cap_color);
+        }
+    }
+}
+
+/// Draws the translucent second ring from `radius`, its bars starting at `arc_start`.
+fn draw_second_ring(ring: &RingGeometry, arc_start: f32, radius: f32, heights: &[f32; BAR_COUNT], theme: &BarTheme) {
+    let length_scale = ring.max_bar_length * SECOND_RING_LENGTH;
+    for (i, &height) in heights.iter().enumerate() {
+        let shown = if height < theme.dead_zone { 0.0 } else { height };
+        let length = height_to_fraction(shown, theme.db_scale) * length_scale;
+        let mut color = bar_color(theme.bar_colors, theme.palettes, height, 0.0, theme.color_gamma);
+        color.a = 0.55;
+        ring.wedge(arc_start, i, radius, radius + length, color);
+    }
+}
+
+/// Draws plain bars of `heights` in one flat `color`, e.g. the long-term average behind the live bars.
+fn draw_bar_ghosts(ring: &RingGeometry, heights: &[f32; BAR_COUNT], db_scale: bool, color: Color) {
+    for (i, &height) in heights.iter().enumerate() {
+        let length = height_to_fraction(height, db_scale) * ring.max_bar_length;
+        ring.wedge(ring.arc_start, i, ring.anchor_radius, ring.tip_radius(length), color);
+    }
+}
+
+/// Joins the tips of `heights` with a line; `closed` links the last bar back to the first.
+fn draw_bar_contour(ring: &RingGeometry, heights: &[f32; BAR_COUNT], db_scale: bool, closed: bool) {
+    let tip = |i: usize| {
+        let radius = ring.tip_radius(height_to_fraction(heights[i], db_scale) * ring.max_bar_length);
+        let angle = ring.arc_start + (i as f32 + 0.5) * ring.angle_step;
+        (ring.center.x + radius * angle.cos(), ring.center.y + radius * angle.sin())
+    };
+    let segments = if closed { BAR_COUNT } else { BAR_COUNT - 1 };
+    for i in 0..segments {
+        let (x0, y0) = tip(i);
+        let (x1, y1) = tip((i + 1) % BAR_COUNT);
+        draw_line(x0, y0, x1, y1, 1.5, Color::new(1.0, 1.0, 1.0, 0.85));
+    }
+}
+
+/// Draws dB reference circles (and their labels) at `DB_GRID_LEVELS` around the ring.
+fn draw_db_grid(ring: &RingGeometry, db_scale: bool, ui: f32) {
+    let grid_color = Color::new(0.35, 0.35, 0.35, 0.6);
+    let (cx, cy) = (ring.center.x, ring.center.y);
+    for &db in DB_GRID_LEVELS.iter() {
+        let fraction = height_to_fraction(10f32.powf(db / 20.0), db_scale);
+        let radius = ring.anchor_radius + ring.bar_dir * fraction * ring.max_bar_length;
+        if fraction * ring.max_bar_length < 4.0 {
+            continue;
+        }
+        draw_circle_lines(cx, cy, radius, 1.0, grid_color);
+        draw_text(&format!("{:.0} dB", db), cx + 4.0 * ui, cy - radius - 3.0 * ui, 12.0 * ui, grid_color);
+    }
+}
+
+/// Draws projectiles and their trails within `region`, growing with distance from `center`
+/// for a sense of depth. `fade` gives each projectile's opacity, 1 for fully visible.
+fn draw_projectiles(
+    projectiles: &[Projectile],
+    region: Rect,
+    center: Vec2,
+    pulse: f32,
+    shape: ProjectileShape,
+    config: &Config,
+    fade: impl Fn(&Projectile) -> f32,
+) {
+    let perspective_ref = (region.w.max(region.h) * 0.55).max(400.0);
+    let perspective_ref_sq = perspective_ref * perspective_ref;
+    // Grows with distance from the center and saturates at perspective_ref (no sqrt once saturated)
+    let perspective_at = |x: f32, y: f32| {
+        let dist_sq = (x - center.x).powi(2) + (y - center.y).powi(2);
+        if dist_sq >= perspective_ref_sq {
+            1.7
+        } else {
+            0.5 + 1.2 * dist_sq.sqrt() / perspective_ref
+        }
+    };
+    // Largest half-size a projectile can be drawn at, so culling never clips a visible square
+    const CULL_MARGIN: f32 = 8.0;
+    for p in projectiles {
+        let (min_x, max_x, min_y, max_y) = p.trail.iter().fold(
+            (p.x, p.x, p.y, p.y),
+            |(x0, x1, y0, y1), &(tx, ty)| (x0.min(tx), x1.max(tx), y0.min(ty), y1.max(ty)),
+        );
+        if max_x < region.x - CULL_MARGIN
+            || min_x > region.x + region.w + CULL_MARGIN
+            || max_y < region.y - CULL_MARGIN
+            || min_y > region.y + region.h + CULL_MARGIN
+        {
+            continue;
+        }
+        let perspective = perspective_at(p.x, p.y);
+        // If the whole trail sits at about the same scale, reuse the head's perspective
+        let uniform_perspective = p
+            .trail
+            .front()
+            .is_none_or(|&(tx, ty)| (perspective_at(tx, ty) - perspective).abs() < 0.02);
+        let fade = fade(p);
+        let trail_color = brighten(hsv_to_color(p.hue, 0.9, 1.0), pulse);
+        let len = p.trail.len() as f32;
+        for (i, &(tx, ty)) in p.trail.iter().enumerate() {
+            let t = i as f32 / len.max(1.0);
+            let (alpha_min, alpha_max) = config.trail_alpha;
+            let alpha = alpha_min + (alpha_max - alpha_min) * t.powf(config.trail_curve);
+            let mut c = trail_color;
+            c.a = alpha * fade;
+            let trail_perspective = if uniform_perspective {
+                perspective
+            } else {
+                perspective_at(tx, ty)
+            };
+            let trail_size = p.size * (0.3 + 0.7 * t) * trail_perspective;
+            draw_dot(shape, tx, ty, trail_size, c);
+        }
+        let mut c = brighten(hsv_to_color(p.hue, 0.95, 1.0), pulse);
+        c.a = fade;
+        draw_dot(shape, p.x, p.y, p.size * perspective, c);
+    }
+}
+
+/// Fraction of each LED segment's length left dark as the gap to the next one.
+const LED_SEGMENT_GAP: f32 = 0.25;
+/// Hue of the lowest LED segment; the top one is red (0°).
+const LED_LOW_HUE: f32 = 120.0;
+/// Color of LED segments above the bar's level.
+const LED_UNLIT: Color = Color::new(1.0, 1.0, 1.0, 0.06);
+
+/// Darkness of the vignette's corners in silence; loud passages open it up completely.
+const VIGNETTE_MAX_ALPHA: f32 = 0.8;
+/// Time constant (s) of the loudness the vignette follows, so it breathes with passages, not hits.
+const VIGNETTE_SMOOTHING: f32 = 0.6;
+/// Segments around the vignette's rings.
+const VIGNETTE_SEGMENTS: usize = 48;
+
+/// Darkens the screen toward its edges: clear inside 40% of the half-diagonal, reaching `alpha`
+/// black at the corners. Drawn as a mesh of concentric rings with per-vertex alpha.
+fn draw_vignette(screen_width: f32, screen_height: f32, alpha: f32) {
+    // (fraction of the half-diagonal, fraction of `alpha`); eased so the falloff has no visible edge
+    const RINGS: [(f32, f32); 4] = [(0.4, 0.0), (0.65, 0.25), (0.85, 0.65), (1.0, 1.0)];
+    let (cx, cy) = (screen_width / 2.0, screen_height / 2.0);
+    let reach = cx.hypot(cy);
+    let mut vertices = Vec::with_capacity(RINGS.len() * VIGNETTE_SEGMENTS);
+    for (r, a) in RINGS {
+        for s in 0..VIGNETTE_SEGMENTS {
+            let angle = s as f32 / VIGNETTE_SEGMENTS as f32 * std::f32::consts::TAU;
+            let (x, y) = (cx + reach * r * angle.cos(), cy + reach * r * angle.sin());
+            vertices.push(Vertex::new(x, y, 0.0, 0.0, 0.0, Color::new(0.0, 0.0, 0.0, alpha * a)));
+        }
+    }
+    let mut indices = Vec::with_capacity((RINGS.len() - 1) * VIGNETTE_SEGMENTS * 6);
+    for ring in 0..RINGS.len() - 1 {
+        for s in 0..VIGNETTE_SEGMENTS {
+            let inner = (ring * VIGNETTE_SEGMENTS + s) as u16;
+            let inner_next = (ring * VIGNETTE_SEGMENTS + (s + 1) % VIGNETTE_SEGMENTS) as u16;
+            let outer = inner + VIGNETTE_SEGMENTS as u16;
+            let outer_next = inner_next + VIGNETTE_SEGMENTS as u16;
+            indices.extend_from_slice(&[inner, outer, outer_next, inner, outer_next, inner_next]);
+        }
+    }
+    draw_mesh(&Mesh {
+        vertices,
+        indices,
+        texture: None,
+    });
+}
+
+/// Every key binding, as shown by the help overlay.
+const HELP_KEYS: &[(&str, &str)] = &[
+    ("H / F1 / ?", "Toggle this help"),
+    ("SPACE", "FPS and audio status"),
+    ("F2", "Settings panel (settings-panel builds)"),
+    ("O", "Move the status overlay to the next corner"),
+    ("S", "Rotate"),
+    ("Up / Down", "Rotation speed (auto: direction)"),
+    ("Q", "Auto-rotate: speed follows the energy"),
+    ("F", "Beat align: nudge a bar to the top on beats"),
+    ("A", "Projectile accel / decel"),
+    ("D", "Projectile slowdown by distance / time"),
+    ("V", "Layout: full circle / half-circle"),
+    ("C", "Projectile colors"),
+    ("P", "Bar colors: palette / gradient / rise"),
+    ("B", "Fire on beats only"),
+    ("M", "Long-term average spectrum"),
+    ("N", "Averaged spectrum contour over live bars"),
+    ("T", "Highlight the dominant frequency"),
+    ("\\", "Nearest musical note of the dominant frequency"),
+    ("W", "Stereo correlation meter"),
+    ("E", "Export the current spectrum to CSV"),
+    ("X", "Mirror projectiles across the vertical axis"),
+    ("Z", "Projectile shape: square / circle"),
+    ("J", "Gravity well: projectiles orbit the center"),
+    ("U", "Inner circle color follows the level"),
+    ("Tab", "Vignette: edges darken in quiet passages"),
+    ("1", "Inset: off / spectrum / waveform"),
+    ("R", "Reset the long-term average"),
+    ("I", "Bars grow inward"),
+    (", / .", "Intensity: calmer / more intense"),
+    ("[ / ]", "Shift vocal boost region"),
+    ("K", "Spectral smoothing"),
+    ("- / =", "Transient threshold (Shift: peak hysteresis)"),
+    ("Y / Shift+Y", "Raise / lower the display dead zone"),
+    ("; / Shift+;", "Widen / narrow the gaps between bars"),
+    ("L", "dB bar scale"),
+    ("G", "dB reference grid"),
+    ("F11", "Fullscreen"),
+];
+
+/// Window height up to which `--ui-scale auto` stays at 1; taller windows scale proportionally.
+const UI_SCALE_REFERENCE_HEIGHT: f32 = 1080.0;
+
+/// Multiplier for overlay font sizes and layout: the configured `--ui-scale`, or derived from
+/// the window height so text stays readable on 4K screens and projectors.
+fn ui_scale(configured: Option<f32>, screen_height: f32) -> f32 {
+    configured.unwrap_or((screen_height / UI_SCALE_REFERENCE_HEIGHT).max(1.0))
+}
+
+/// Draws a translucent panel centered on screen: key bindings on the left,
+/// current mode states on the right. Every size is multiplied by `ui`.
+fn draw_help_overlay(
+    states: &[(&str, String)],
+    palettes: &[config::CustomPalette],
+    screen_width: f32,
+    screen_height: f32,
+    ui: f32,
+) {
+    let line = 20.0 * ui;
+    let key_col = 110.0 * ui;
+    let state_col = 470.0 * ui;
+    let width = 760.0 * ui;
+    let rows = HELP_KEYS.len().max(states.len()) + 1;
+    let h = rows as f32 * line + 24.0 * ui;
+    let x = ((screen_width - width) / 2.0).max(0.0);
+    let y = ((screen_height - h) / 2.0).max(0.0);
+    draw_rectangle(x, y, width, h, Color::new(0.0, 0.0, 0.0, 0.75));
+    draw_rectangle_lines(x, y, width, h, 1.0, GRAY);
+
+    // Loaded --palettes follow the built-in bar colors in the P cycle
+    let mut keys = HELP_KEYS.to_vec();
+    let bar_colors_help;
+    if let Some((_, action)) = keys.iter_mut().find(|(key, _)| *key == "P") {
+        bar_colors_help = palettes.iter().fold(action.to_string(), |help, p| help + " / " + &p.name);
+        *action = &bar_colors_help;
+    }
+    let states: Vec<(&str, &str)> = states.iter().map(|(name, value)| (*name, value.as_str())).collect();
+    for (col_x, title, rows) in [(x + 16.0 * ui, "Controls", &keys[..]), (x + state_col, "Current", &states[..])] {
+        let mut ty = y + 28.0 * ui;
+        draw_text(title, col_x, ty, 22.0 * ui, WHITE);
+        for (name, value) in rows {
+            ty += line;
+            draw_text(name, col_x, ty, 18.0 * ui, YELLOW);
+            draw_text(value, col_x + key_col, ty, 18.0 * ui, LIGHTGRAY);
+        }
+    }
+}
+
+/// Draws a horizontal -1..+1 stereo correlation meter with its top-left corner at (x, y),
+/// sized by `ui`. `value` is `None` when there's no stereo signal to measure.
+fn draw_correlation_meter(value: Option<f32>, x: f32, y: f32, ui: f32) {
+    let width = 200.0 * ui;
+    let height = 10.0 * ui;
+    let font = 14.0 * ui;
+    draw_rectangle(x, y, width, height, Color::new(0.15, 0.15, 0.15, 0.8));
+    draw_line(x + width / 2.0, y - 2.0 * ui, x + width / 2.0, y + height + 2.0 * ui, 1.0, GRAY);
+    draw_text("-1", x - 16.0 * ui, y + height, font, GRAY);
+    draw_text("+1", x + width + 4.0 * ui, y + height, font, GRAY);
+    let Some(value) = value else {
+        draw_text("Correlation: no stereo signal", x, y + height + 16.0 * ui, font, GRAY);
+        return;
+    };
+    // Negative correlation means phase problems that will cancel in mono
+    let color = if value < 0.0 {
+        RED
+    } else if value < 0.3 {
+        YELLOW
+    } else {
+        GREEN
+    };
+    let needle_x = x + (value + 1.0) / 2.0 * width;
+    draw_rectangle(needle_x - 2.0 * ui, y - 3.0 * ui, 4.0 * ui, height + 6.0 * ui, color);
+    draw_text(&format!("Correlation: {:+.2}", value), x, y + height + 16.0 * ui, font, color);
+}
+
+/// How much louder another bar must be than the tracked one before the peak tracker moves to it.
+const TRACKER_HYSTERESIS: f32 = 1.1;
+
+/// Formats a frequency as Hz below 1 kHz and kHz above.
+fn format_freq(hz: f32) -> String {
+    if hz < 1000.0 {
+        format!("{:.0} Hz", hz)
+    } else {
+        format!("{:.2} kHz", hz / 1000.0)
+    }
+}
+
+const NOTE_NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+/// Nearest equal-tempered note to `hz` with A4 tuned to `a4` Hz, e.g. `("A4", 12.0)`, and how
+/// far `hz` is from it in cents (-50..50; positive is sharp).
+fn freq_to_note(hz: f32, a4: f32) -> (String, f32) {
+    let semitones = 12.0 * (hz / a4).log2();
+    let nearest = semitones.round();
+    // MIDI numbering: A4 is 69 and octaves start at C
+    let midi = 69 + nearest as i32;
+    let name = NOTE_NAMES[midi.rem_euclid(12) as usize];
+    (format!("{}{}", name, midi.div_euclid(12) - 1), (semitones - nearest) * 100.0)
+}
+
+/// Time constant (s) for the note readout to follow small pitch changes; a jump of a semitone or
+/// more is taken at once.
+const NOTE_SMOOTHING: f32 = 0.15;
+
+/// Offset of the last status overlay line below the first; bottom anchors reserve this much.
+const OVERLAY_HEIGHT: f32 = 117.0;
+
+/// Draws status lines as a block in the given corner. Each line is
+/// `(baseline offset below the first line, text, font size, color)` at a UI scale of 1;
+/// offsets, sizes and margins are multiplied by `ui`.
+fn draw_overlay_text(
+    lines: &[(f32, String, f32, Color)],
+    anchor: OverlayAnchor,
+    screen_width: f32,
+    screen_height: f32,
+    ui: f32,
+) {
+    let margin = 10.0 * ui;
+    let top = if anchor.is_bottom() {
+        screen_height - margin - OVERLAY_HEIGHT * ui
+    } else {
+        30.0 * ui
+    };
+    for (dy, text, size, color) in lines {
+        let size = size * ui;
+        let x = if anchor.is_right() {
+            screen_width - margin - measure_text(text, None, size as u16, 1.0).width
+        } else {
+            margin
+        };
+        draw_text(text, x, top + dy * ui, size, *color);
+    }
+}
+
+/// Status label for what is firing projectiles.
+fn firing_label(beats_only: bool, bass_autofire: bool) -> &'static str {
+    match (beats_only, bass_autofire) {
+        (true, _) => "beats",
+        (false, true) => "peaks",
+        (false, false) => "transients",
+    }
+}
+
+/// Gap between the picture-in-picture inset and the window edges.
+const INSET_MARGIN: f32 = 12.0;
+/// Trace color of the waveform inset.
+const INSET_WAVE_COLOR: Color = Color::new(0.6, 0.9, 1.0, 0.9);
+
+/// Screen rectangle of the inset: `size` of the window's width and height, in `corner`.
+fn inset_rect(corner: OverlayAnchor, size: f32, screen_width: f32, screen_height: f32) -> Rect {
+    let (w, h) = (screen_width * size, screen_height * size);
+    let x = if corner.is_right() { screen_width - INSET_MARGIN - w } else { INSET_MARGIN };
+    let y = if corner.is_bottom() { screen_height - INSET_MARGIN - h } else { INSET_MARGIN };
+    Rect::new(x, y, w, h)
+}
+
+/// Draws the bars left to right as a linear spectrum filling `rect`, bass on the left.
+fn draw_spectrum(rect: Rect, heights: &[f32; BAR_COUNT], db_scale: bool, color_of: impl Fn(f32) -> Color) {
+    let step = rect.w / BAR_COUNT as f32;
+    for (i, &height) in heights.iter().enumerate() {
+        let length = height_to_fraction(height, db_scale) * rect.h;
+        let x = rect.x + i as f32 * step;
+        draw_rectangle(x, rect.y + rect.h - length, (step - 1.0).max(1.0), length, color_of(height));
+    }
+}
+
+/// Draws `samples` as an oscilloscope trace across `rect`, one point per pixel column at most.
+fn draw_waveform(rect: Rect, samples: &[f32], color: Color) {
+    if samples.len() < 2 {
+        return;
+    }
+    let points = (rect.w as usize).clamp(2, samples.len());
+    let mid = rect.y + rect.h / 2.0;
+    // Chunks are normalized to a 0.4 peak, so doubling fills most of the height
+    let point = |k: usize| {
+        let s = samples[k * (samples.len() - 1) / (points - 1)];
+        (
+            rect.x + rect.w * k as f32 / (points - 1) as f32,
+            mid - (s * 2.0).clamp(-1.0, 1.0) * rect.h / 2.0,
+        )
+    };
+    for k in 1..points {
+        let ((x0, y0), (x1, y1)) = (point(k - 1), point(k));
+        draw_line(x0, y0, x1, y1, 1.0, color);
+    }
+}
+
+/// Distance from the bottom of the window to the center of the half-circle layout.
+const HALF_CIRCLE_MARGIN: f32 = 20.0;
+
+/// Radius of the inner circle when it isn't reacting to energy.
+const INNER_RADIUS: f32 = 40.0;
+
+/// Length of second-ring bars as a fraction of the main ring's `max_bar_length`.
+const SECOND_RING_LENGTH: f32 = 0.35;
+
+/// Positions kept in each projectile's trail at intensity 1.0.
+const TRAIL_LEN: usize = 12;
+
+/// Fraction of the way the input normalization gain moves toward each chunk's target:
+/// fast when the signal gets louder (gain falls), slow when it gets quieter, so it doesn't pump.
+const INPUT_GAIN_ATTACK: f32 = 0.5;
+const INPUT_GAIN_RELEASE: f32 = 0.05;
+
+/// Raw chunk peak below which input counts as silence for `--silence-timeout` (about -80 dBFS).
+const SILENCE_PEAK: f32 = 1e-4;
+/// Time constant of the fade to the stopped state, in seconds.
+const SILENCE_FADE: f32 = 0.4;
+
+/// Most chunks analyzed per frame; after a hitch older queued chunks are dropped unanalyzed.
+const MAX_CHUNKS_PER_FRAME: usize = 3;
+
+/// Width of the band above the speed floor, as a fraction of the floor, over which a
+/// decelerating projectile's slowdown fades out.
+const SPEED_FLOOR_KNEE: f32 = 0.5;
+
+/// Next speed of a projectile moving at `speed` px/s, slowing down (`decel`) or speeding up by
+/// `rate` (already scaled by the frame time). The slowdown fades out smoothly through the knee
+/// above `floor`, so projectiles ease onto the floor instead of hitting it with a visible kink.
+/// Never returns less than `floor`; slower launches are lifted straight to it.
+fn projectile_speed(speed: f32, rate: f32, decel: bool, floor: f32) -> f32 {
+    let next = if decel {
+        let x = ((speed - floor) / (floor * SPEED_FLOOR_KNEE).max(1.0)).clamp(0.0, 1.0);
+        // Smoothstep, so the slowdown has no corner where the knee starts either
+        let ease = x * x * (3.0 - 2.0 * x);
+        speed / (1.0 + rate * ease)
+    } else {
+        speed * (1.0 + rate)
+    };
+    next.max(floor)
+}
+
+/// Gravity well: pull strength G of the inverse-distance attraction (acceleration G / r), so
+/// circular orbits all run at sqrt(G) px/s. Launches at twice that speed swing out several
+/// times their launch radius before falling back.
+const ORBIT_STRENGTH: f32 = 90.0 * 90.0;
+/// Angle between the outward radius and the launch direction; near 90° means mostly tangential.
+const ORBIT_LAUNCH_ANGLE: f32 = 1.2;
+/// Velocity damping per second, so orbits decay and spiral into the center.
+const ORBIT_DRAG: f32 = 0.12;
+/// Seconds before an orbiting projectile is retired even if it hasn't fallen in.
+const ORBIT_LIFETIME: f32 = 15.0;
+/// Seconds over which a projectile fades out before its lifetime runs out.
+const LIFETIME_FADE: f32 = 0.5;
+
+/// Range and step of the intensity knob that scales spawn rate, trail length and hue speed.
+const MIN_INTENSITY: f32 = 0.25;
+const MAX_INTENSITY: f32 = 2.0;
+const INTENSITY_STEP: f32 = 0.25;
+/// Trail capacity at full intensity, so longer trails never reallocate.
+const MAX_TRAIL_LEN: usize = (TRAIL_LEN as f32 * MAX_INTENSITY) as usize;
+
 struct Projectile {
     x: f32,
     y: f32,
@@ -103,33 +858,278 @@ struct Projectile {
     dy: f32,
     hue: f32,
     size: f32,
-    trail: Vec<(f32, f32)>,
+    /// Recent positions, oldest first; fixed capacity so it never reallocates.
+    trail: VecDeque<(f32, f32)>,
     birth_time: f32,
 }
 
+/// How the bars divide into the bass, mid and high regions, and where those regions meet.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct BandSplit {
+    bass_bars: usize,
+    mid_bars: usize,
+    high_bars: usize,
+    /// Bass/mid boundary, Hz.
+    bass_mid_hz: f32,
+    /// Mid/high boundary, Hz.
+    mid_high_hz: f32,
+}
+
+impl Default for BandSplit {
+    fn default() -> Self {
+        Self {
+            bass_bars: 10,
+            mid_bars: 44,
+            high_bars: 10,
+            bass_mid_hz: 500.0,
+            mid_high_hz: 3500.0,
+        }
+    }
+}
+
+impl BandSplit {
+    /// Takes `--bands` and `--band-edges` as validated by config parsing: non-zero bar
+    /// counts summing to `BAR_COUNT` and ascending boundaries.
+    fn new(bars: [usize; 3], edges: (f32, f32)) -> Self {
+        let [bass_bars, mid_bars, high_bars] = bars;
+        let (bass_mid_hz, mid_high_hz) = edges;
+        Self {
+            bass_bars,
+            mid_bars,
+            high_bars,
+            bass_mid_hz,
+            mid_high_hz,
+        }
+    }
+}
+
 struct VisualizerState {
     bar_heights: [f32; BAR_COUNT],
     peak_heights: [f32; BAR_COUNT],
+    /// Running mean of each bar's height since the last reset, over `average_chunks` chunks.
+    average_heights: [f32; BAR_COUNT],
+    average_chunks: u32,
+    /// Target minus previous height for each bar in the last update; positive while rising.
+    bar_deltas: [f32; BAR_COUNT],
+    /// Center frequency of each bar's band in Hz, as laid out by the last update.
+    bar_centers: [f32; BAR_COUNT],
     peak_fired: Vec<usize>,
     fire_cooldown: [u8; BAR_COUNT],
+    /// Per-bar highlight, 1.0 when the bar registers a transient; decays in the render loop.
+    bar_flash: [f32; BAR_COUNT],
     fft_input: Vec<Complex<f32>>,
+    /// Magnitude spectrum of the last chunk (FFT_SIZE / 2 bins), after optional smoothing
+    /// and frame averaging.
+    magnitudes: Vec<f32>,
+    /// Number of recent spectra averaged before band reduction; 1 disables averaging.
+    spectrum_frames: usize,
+    /// The last `spectrum_frames` spectra, oldest first.
+    spectrum_history: VecDeque<Vec<f32>>,
+    /// Mean of `spectrum_history`; drives the bars unless `average_overlay` is set.
+    averaged_magnitudes: Vec<f32>,
+    /// Keep the bars on the live spectrum and reduce the averaged one into `averaged_heights`.
+    average_overlay: bool,
+    /// Bar levels of the averaged spectrum, for the overlay contour.
+    averaged_heights: [f32; BAR_COUNT],
+    smoothing_scratch: Vec<f32>,
+    prev_magnitudes: Vec<f32>,
+    /// Spectral flux of the last chunk: newly appeared magnitude relative to the total (0..1).
+    flux: f32,
+    flux_avg: f32,
+    /// Onset pulse (1.0 on a strong onset); decays in the render loop.
+    pulse: f32,
+    /// Flux must exceed its running average by this factor to count as an onset.
+    pulse_sensitivity: f32,
+    /// Set when a chunk registers an onset; the render loop clears it once handled.
+    beat: bool,
+    /// Radius of the triangular kernel applied across neighboring bins; 0 disables it.
+    spectral_smoothing: usize,
     fft: Arc<dyn Fft<f32>>,
     peak_magnitude: f32,
+    /// Recent per-chunk max magnitudes; the AGC reference is a percentile over these.
+    agc_history: VecDeque<f32>,
+    /// Smoothed overall loudness: RMS of the per-bar targets (0..1), relative to the AGC.
+    energy: f32,
+    /// Lowest frequency shown (bottom of the bass region), Hz.
+    min_freq: f32,
+    /// Highest frequency shown (top of the high region, clamped to Nyquist), Hz.
+    max_freq: f32,
+    bands: BandSplit,
+    band_reduce: BandReduce,
+    /// Rate the analyzed audio was captured at, as reported by the backend, Hz.
+    sample_rate: f32,
+    /// Envelope time constants in seconds; the per-chunk coefficients below are derived from them.
+    attack_time: f32,
+    release_time: f32,
+    /// Fraction of the gap to the target a rising bar closes per chunk (1.0 = instant).
+    bar_attack: f32,
+    /// Fraction of the gap to the target a falling bar closes per chunk.
+    bar_release: f32,
+    /// How far a target must clear the current peak before it counts as a new peak.
+    peak_hysteresis: f32,
+    /// How far a new peak must jump above the bar's previous height to fire as a transient.
+    transient_threshold: f32,
+    /// Bass bars fire on every new peak, not only on transients.
+    bass_autofire: bool,
+    /// Shift (in mid bars) applied to the vocal boost regions; positive moves them up in pitch.
+    vocal_offset: i32,
+}
+
+/// FFT bins `start..end` covering `f_start..f_end` Hz at `freq_per_bin`; never empty, so a bar
+/// narrower than a bin still reads the bin it falls in.
+fn band_bin_range(f_start: f32, f_end: f32, freq_per_bin: f32) -> (usize, usize) {
+    let bins = FFT_SIZE / 2;
+    let start = ((f_start / freq_per_bin) as usize).min(bins.saturating_sub(1));
+    let end = ((f_end / freq_per_bin) as usize).min(bins).max(start + 1);
+    (start, end)
 }
 
+/// Spectrum frames the average overlay uses when `--spectrum-average` is off (~0.75 s).
+const OVERLAY_AVERAGE_FRAMES: usize = 16;
+
+/// Chunks of history kept for the AGC (~6 s at 44.1 kHz / 2048).
+const AGC_HISTORY_LEN: usize = 128;
+/// Percentile of the AGC history used as the gain reference, so lone transients don't dominate.
+const AGC_PERCENTILE: f32 = 0.9;
+
+/// Furthest the vocal boost regions can be nudged either way, in mid bars.
+const MAX_VOCAL_OFFSET: i32 = 16;
+
 impl VisualizerState {
+    /// A fresh analyzer with the DSP settings from the command line applied.
+    fn from_config(config: &Config) -> Self {
+        let mut state = Self::new();
+        state.spectral_smoothing = config.spectral_smoothing;
+        state.set_average_overlay(config.average_overlay, config.spectrum_average);
+        state.pulse_sensitivity = config.pulse_sensitivity;
+        state.peak_hysteresis = config.peak_hysteresis;
+        state.transient_threshold = config.transient_threshold;
+        state.bass_autofire = config.fire_on != FireOn::Transients;
+        state.min_freq = config.min_freq;
+        state.max_freq = config.max_freq;
+        state.bands = BandSplit::new(config.bands, config.band_edges);
+        state.band_reduce = config.band_reduce;
+        state.set_envelope(config.attack, config.release);
+        state
+    }
+
     fn new() -> Self {
         let mut planner = FftPlanner::new();
         let fft = planner.plan_fft_forward(FFT_SIZE);
         Self {
             bar_heights: [0.0; BAR_COUNT],
             peak_heights: [0.0; BAR_COUNT],
+            average_heights: [0.0; BAR_COUNT],
+            average_chunks: 0,
+            bar_deltas: [0.0; BAR_COUNT],
+            bar_centers: [0.0; BAR_COUNT],
             peak_fired: Vec::new(),
             fire_cooldown: [0; BAR_COUNT],
+            bar_flash: [0.0; BAR_COUNT],
             fft_input: vec![Complex::new(0.0, 0.0); FFT_SIZE],
+            magnitudes: vec![0.0; FFT_SIZE / 2],
+            spectrum_frames: 1,
+            spectrum_history: VecDeque::new(),
+            averaged_magnitudes: vec![0.0; FFT_SIZE / 2],
+            average_overlay: false,
+            averaged_heights: [0.0; BAR_COUNT],
+            smoothing_scratch: vec![0.0; FFT_SIZE / 2],
+            prev_magnitudes: vec![0.0; FFT_SIZE / 2],
+            flux: 0.0,
+            flux_avg: 0.0,
+            pulse: 0.0,
+            pulse_sensitivity: 1.5,
+            beat: false,
+            spectral_smoothing: 0,
             fft,
             peak_magnitude: 0.01,
+            agc_history: VecDeque::with_capacity(AGC_HISTORY_LEN),
+            energy: 0.0,
+            min_freq: 20.0,
+            max_freq: 15000.0,
+            bands: BandSplit::default(),
+            band_reduce: BandReduce::Mix,
+            sample_rate: SAMPLE_RATE as f32,
+            attack_time: 0.05,
+            release_time: 0.2,
+            bar_attack: 0.6,
+            bar_release: 0.2,
+            peak_hysteresis: 0.05,
+            transient_threshold: 0.025,
+            bass_autofire: true,
+            vocal_offset: 0,
+        }
+    }
+
+    /// Sets the bar envelope from attack/release time constants in seconds. Bars update once per
+    /// FFT chunk, so the per-chunk coefficients depend only on the chunk length, not the frame rate.
+    fn set_envelope(&mut self, attack: f32, release: f32) {
+        let chunk = FFT_SIZE as f32 / self.sample_rate;
+        let coeff = |tau: f32| 1.0 - (-chunk / tau.max(1e-4)).exp();
+        self.attack_time = attack;
+        self.release_time = release;
+        self.bar_attack = coeff(attack);
+        self.bar_release = coeff(release);
+    }
+
+    /// Follows the rate the backend actually negotiated, so bins map to the right frequencies.
+    fn set_sample_rate(&mut self, rate: u32) {
+        if rate == 0 || rate as f32 == self.sample_rate {
+            return;
         }
+        self.sample_rate = rate as f32;
+        // Chunks last a different time now, so the envelope coefficients change with them
+        self.set_envelope(self.attack_time, self.release_time);
+    }
+
+    /// Frequency of the strongest bin between `min_freq` and `max_freq`, refined by parabolic
+    /// interpolation with its neighbors to well under a bin; `None` when there's no signal.
+    fn dominant_freq(&self) -> Option<f32> {
+        let bin_hz = self.sample_rate / FFT_SIZE as f32;
+        let lo = ((self.min_freq / bin_hz) as usize).max(1);
+        let hi = ((self.max_freq / bin_hz) as usize).min(self.magnitudes.len() - 2);
+        let k = (lo..=hi).max_by(|&a, &b| self.magnitudes[a].total_cmp(&self.magnitudes[b]))?;
+        let (a, b, c) = (self.magnitudes[k - 1], self.magnitudes[k], self.magnitudes[k + 1]);
+        if b <= 1e-6 {
+            return None;
+        }
+        let curvature = a - 2.0 * b + c;
+        let offset = if curvature < 0.0 { 0.5 * (a - c) / curvature } else { 0.0 };
+        Some((k as f32 + offset) * bin_hz)
+    }
+
+    /// Frequency span (start, end) in Hz of bar `i`. Each region is spaced geometrically; by
+    /// default Bass+Low 20-500Hz (10) | Mids 500-3.5kHz (44) | High 3.5k-15kHz (10), so the
+    /// vocal-centric mids dominate the circle and the extremes sit at its edges.
+    fn band_range(&self, i: usize) -> (f32, f32) {
+        let BandSplit {
+            bass_bars,
+            mid_bars,
+            high_bars,
+            bass_mid_hz,
+            mid_high_hz,
+        } = self.bands;
+        let (lo, hi, j, count) = if i < bass_bars {
+            (self.min_freq, bass_mid_hz, i, bass_bars)
+        } else if i < bass_bars + mid_bars {
+            (bass_mid_hz, mid_high_hz, i - bass_bars, mid_bars)
+        } else {
+            let nyquist = self.sample_rate / 2.0;
+            (mid_high_hz, self.max_freq.min(nyquist), i - bass_bars - mid_bars, high_bars)
+        };
+        let t0 = j as f32 / count as f32;
+        let t1 = (j + 1) as f32 / count as f32;
+        (lo * (hi / lo).powf(t0), lo * (hi / lo).powf(t1))
+    }
+
+    /// FFT bins `start..end` that bar `i` reduces at the current sample rate.
+    fn band_bins(&self, i: usize) -> (usize, usize) {
+        let (f_start, f_end) = self.band_range(i);
+        band_bin_range(f_start, f_end, self.sample_rate / FFT_SIZE as f32)
+    }
+
+    fn nudge_vocal_offset(&mut self, step: i32) {
+        self.vocal_offset = (self.vocal_offset + step).clamp(-MAX_VOCAL_OFFSET, MAX_VOCAL_OFFSET);
     }
 
     fn update(&mut self, audio_data: &[f32]) {
@@ -144,95 +1144,109 @@ impl VisualizerState {
         }
 
         self.fft.process(&mut self.fft_input);
+        for (m, c) in self.magnitudes.iter_mut().zip(&self.fft_input) {
+            *m = c.norm();
+        }
+        if self.spectral_smoothing > 0 {
+            smooth_spectrum(&self.magnitudes, &mut self.smoothing_scratch, self.spectral_smoothing);
+            std::mem::swap(&mut self.magnitudes, &mut self.smoothing_scratch);
+        }
+
+        let mut rise = 0.0;
+        let mut total = 0.0;
+        for (&m, prev) in self.magnitudes.iter().zip(self.prev_magnitudes.iter_mut()) {
+            rise += (m - *prev).max(0.0);
+            total += m;
+            *prev = m;
+        }
+        self.flux = if total > 1e-6 { rise / total } else { 0.0 };
+        if self.flux > 0.05 && self.flux > self.flux_avg * self.pulse_sensitivity {
+            self.pulse = 1.0;
+            self.beat = true;
+        }
+        self.flux_avg = self.flux_avg * 0.9 + self.flux * 0.1;
+
+        // Averaged after the flux so onsets still come from the live spectrum
+        if self.spectrum_frames > 1 {
+            // The window can shrink when the overlay is switched off
+            while self.spectrum_history.len() > self.spectrum_frames {
+                self.spectrum_history.pop_front();
+            }
+            let mut frame = if self.spectrum_history.len() >= self.spectrum_frames {
+                self.spectrum_history.pop_front().unwrap_or_default()
+            } else {
+                Vec::with_capacity(FFT_SIZE / 2)
+            };
+            frame.clear();
+            frame.extend_from_slice(&self.magnitudes);
+            self.spectrum_history.push_back(frame);
+            let scale = 1.0 / self.spectrum_history.len() as f32;
+            for (bin, m) in self.averaged_magnitudes.iter_mut().enumerate() {
+                *m = self.spectrum_history.iter().map(|f| f[bin]).sum::<f32>() * scale;
+            }
+            if !self.average_overlay {
+                self.magnitudes.copy_from_slice(&self.averaged_magnitudes);
+            }
+        }
 
-        const SAMPLE_RATE: f32 = 44100.0;
         let bins = FFT_SIZE / 2;
-        let nyquist = SAMPLE_RATE / 2.0;
-        let freq_per_bin = SAMPLE_RATE / FFT_SIZE as f32;
-
-        // Vocal-centric: mids dominate the circle, extremes (bass/highs) at edges.
-        // Bass+Low 20-500Hz (10) | Mids 500-3.5kHz (44) | High 3.5k-10kHz (10)
-        const BASS_LOW_BARS: usize = 10;
-        const MIDS_BARS: usize = 44;
-        const HIGH_BARS: usize = 10;
-        let (f_bass_low_lo, f_bass_low_hi) = (20.0, 500.0);
-        let (f_mids_lo, f_mids_hi) = (500.0, 3500.0);
-        let (f_high_lo, f_high_hi) = (3500.0, 15000.0_f32.min(nyquist));
+        let freq_per_bin = self.sample_rate / FFT_SIZE as f32;
+        let BandSplit {
+            bass_bars,
+            mid_bars,
+            high_bars,
+            ..
+        } = self.bands;
 
         // Use max only from 800 Hz+ so bass doesn't crush gain for percussive highs
         let bass_cutoff_bin = (800.0 / freq_per_bin) as usize;
-        let max_mag: f32 = self.fft_input[bass_cutoff_bin.min(bins)..bins]
+        let max_mag: f32 = self.magnitudes[bass_cutoff_bin.min(bins)..bins]
             .iter()
-            .map(|c: &Complex<f32>| c.norm())
-            .fold(0.0f32, f32::max);
-        self.peak_magnitude = self.peak_magnitude * 0.995 + max_mag * 0.005;
+            .fold(0.0f32, |a, &m| a.max(m));
+        if self.agc_history.len() == AGC_HISTORY_LEN {
+            self.agc_history.pop_front();
+        }
+        self.agc_history.push_back(max_mag);
+        let mut sorted: Vec<f32> = self.agc_history.iter().copied().collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let idx = ((sorted.len() - 1) as f32 * AGC_PERCENTILE).round() as usize;
+        self.peak_magnitude = sorted[idx];
         let gain = if self.peak_magnitude > 0.0001 {
             0.21 / self.peak_magnitude
         } else {
             500.0
         };
 
+        let mut energy_sum = 0.0;
         for i in 0..BAR_COUNT {
-            let (f_start, f_end) = if i < BASS_LOW_BARS {
-                let j = i as f32;
-                let t0 = j / BASS_LOW_BARS as f32;
-                let t1 = (j + 1.0) / BASS_LOW_BARS as f32;
-                (
-                    f_bass_low_lo * (f_bass_low_hi / f_bass_low_lo as f32).powf(t0),
-                    f_bass_low_lo * (f_bass_low_hi / f_bass_low_lo as f32).powf(t1),
-                )
-            } else if i < BASS_LOW_BARS + MIDS_BARS {
-                let j = (i - BASS_LOW_BARS) as f32;
-                let t0 = j / MIDS_BARS as f32;
-                let t1 = (j + 1.0) / MIDS_BARS as f32;
-                (
-                    f_mids_lo * (f_mids_hi / f_mids_lo as f32).powf(t0),
-                    f_mids_lo * (f_mids_hi / f_mids_lo as f32).powf(t1),
-                )
-            } else {
-                let j = (i - BASS_LOW_BARS - MIDS_BARS) as f32;
-                let t0 = j / HIGH_BARS as f32;
-                let t1 = (j + 1.0) / HIGH_BARS as f32;
-                (
-                    f_high_lo * (f_high_hi / f_high_lo as f32).powf(t0),
-                    f_high_lo * (f_high_hi / f_high_lo as f32).powf(t1),
-                )
-            };
-
-            let start = ((f_start / freq_per_bin) as usize).min(bins.saturating_sub(1));
-            let end = ((f_end / freq_per_bin) as usize).min(bins).max(start + 1);
+            let (f_start, f_end) = self.band_range(i);
+            let (start, end) = self.band_bins(i);
 
-            let band_max: f32 = self.fft_input[start..end]
-                .iter()
-                .map(|c: &Complex<f32>| c.norm())
-                .fold(0.0f32, f32::max);
-            let band_avg: f32 = self.fft_input[start..end]
-                .iter()
-                .map(|c: &Complex<f32>| c.norm())
-                .sum::<f32>()
-                / (end - start) as f32;
-            let mag = band_max * 0.4 + band_avg * 0.6;
+            let mag = reduce_band(&self.magnitudes[start..end], self.band_reduce);
 
             // Attenuate bass/low, boost mids (center), boost highs; extra for vocal + percussive presence (2-5 kHz)
-            let tilt = if i < BASS_LOW_BARS {
-                0.22 + 0.15 * (i as f32 / BASS_LOW_BARS as f32)
-            } else if i < BASS_LOW_BARS + MIDS_BARS {
-                let mid_j = (i - BASS_LOW_BARS) as f32;
-                let base = 1.0 + 0.4 * (mid_j / MIDS_BARS as f32);
-                let vocal_boost = if mid_j >= 28.0 && mid_j <= 43.0 {
+            let tilt = if i < bass_bars {
+                0.22 + 0.15 * (i as f32 / bass_bars as f32)
+            } else if i < bass_bars + mid_bars {
+                let mid_j = (i - bass_bars) as f32;
+                let base = 1.0 + 0.4 * (mid_j / mid_bars as f32);
+                // Vocal regions are laid out for the default 44 mid bars
+                let vocal_j = mid_j * 44.0 / mid_bars as f32 - self.vocal_offset as f32;
+                let vocal_boost = if (28.0..=43.0).contains(&vocal_j) {
                     1.9
-                } else if mid_j >= 12.0 && mid_j <= 40.0 {
+                } else if (12.0..=40.0).contains(&vocal_j) {
                     1.75
                 } else {
                     1.0
                 };
                 base * vocal_boost
             } else {
-                let high_j = (i - BASS_LOW_BARS - MIDS_BARS) as f32;
-                2.4 + 2.2 * (high_j / HIGH_BARS as f32)
+                let high_j = (i - bass_bars - mid_bars) as f32;
+                2.4 + 2.2 * (high_j / high_bars as f32)
             };
 
             let f_center = (f_start + f_end) / 2.0;
+            self.bar_centers[i] = f_center;
             let guitar_cut = if (180.0..520.0).contains(&f_center) {
                 0.72
             } else if (800.0..4200.0).contains(&f_center) {
@@ -243,27 +1257,116 @@ impl VisualizerState {
             let tilt = tilt * guitar_cut;
 
             let target_height = (mag * gain * tilt).clamp(0.0, 1.0);
-            self.bar_heights[i] = self.bar_heights[i] * 0.8 + target_height * 0.2;
+            if self.average_overlay {
+                let averaged = reduce_band(&self.averaged_magnitudes[start..end], self.band_reduce);
+                self.averaged_heights[i] = (averaged * gain * tilt).clamp(0.0, 1.0);
+            }
+            let prev_height = self.bar_heights[i];
+            self.bar_deltas[i] = target_height - prev_height;
+            let coeff = if target_height > prev_height {
+                self.bar_attack
+            } else {
+                self.bar_release
+            };
+            self.bar_heights[i] = prev_height + (target_height - prev_height) * coeff;
+            energy_sum += target_height * target_height;
 
-            const PEAK_HYSTERESIS: f32 = 0.05;
-            if target_height > self.peak_heights[i] + PEAK_HYSTERESIS {
+            if target_height > self.peak_heights[i] + self.peak_hysteresis {
                 self.peak_heights[i] = self.peak_heights[i] * 0.6 + target_height * 0.4;
-                let is_transient = target_height > self.bar_heights[i] + 0.02;
-                let is_bass = i < 10;
+                // Measured against the pre-smoothing height so the attack setting doesn't change firing
+                let is_transient = target_height > prev_height + self.transient_threshold;
+                let is_bass = self.bass_autofire && i < bass_bars;
+                if is_transient {
+                    self.bar_flash[i] = 1.0;
+                }
                 if self.fire_cooldown[i] == 0 && (is_transient || is_bass) {
                     self.peak_fired.push(i);
-                    self.fire_cooldown[i] = 8;
+                    self.fire_cooldown[i] = FIRE_COOLDOWN;
                 }
             }
         }
+        let rms = (energy_sum / BAR_COUNT as f32).sqrt();
+        self.energy = self.energy * 0.85 + rms * 0.15;
+
+        self.average_chunks = self.average_chunks.saturating_add(1);
+        let n = self.average_chunks as f32;
+        for (avg, &h) in self.average_heights.iter_mut().zip(&self.bar_heights) {
+            *avg += (h - *avg) / n;
+        }
+    }
+
+    /// Switches the averaged-spectrum overlay. The contour needs an average to draw, so without
+    /// `--spectrum-average` the overlay averages over `OVERLAY_AVERAGE_FRAMES` instead.
+    fn set_average_overlay(&mut self, on: bool, configured_frames: usize) {
+        self.average_overlay = on;
+        self.spectrum_frames = if on {
+            configured_frames.max(OVERLAY_AVERAGE_FRAMES)
+        } else {
+            configured_frames
+        };
+        self.averaged_heights = [0.0; BAR_COUNT];
+    }
+
+    /// Starts the long-term average over from the next chunk.
+    fn reset_average(&mut self) {
+        self.average_heights = [0.0; BAR_COUNT];
+        self.average_chunks = 0;
     }
 
+    /// Clears bars, peaks and spectral history back to their initial state.
+    #[cfg(test)]
     fn reset_bars(&mut self) {
         self.bar_heights = [0.0; BAR_COUNT];
+        self.bar_deltas = [0.0; BAR_COUNT];
         self.peak_heights = [0.0; BAR_COUNT];
         self.peak_fired.clear();
         self.fire_cooldown = [0; BAR_COUNT];
+        self.bar_flash = [0.0; BAR_COUNT];
         self.peak_magnitude = 0.01;
+        self.agc_history.clear();
+        self.energy = 0.0;
+        // Spectral history feeds the next chunk's flux; stale values would fake an onset
+        self.magnitudes.fill(0.0);
+        self.spectrum_history.clear();
+        self.averaged_magnitudes.fill(0.0);
+        self.averaged_heights = [0.0; BAR_COUNT];
+        self.prev_magnitudes.fill(0.0);
+        self.flux = 0.0;
+        self.flux_avg = 0.0;
+        self.pulse = 0.0;
+        self.beat = false;
+        self.reset_average();
+    }
+
+    /// Fades bars, peaks and loudness toward zero, snapping to zero once nothing is visible;
+    /// `fade` is the time constant in seconds.
+    fn fade_out(&mut self, dt: f32, fade: f32) {
+        let k = (-dt / fade.max(0.001)).exp();
+        for (h, p) in self.bar_heights.iter_mut().zip(&mut self.peak_heights) {
+            *h *= k;
+            *p *= k;
+            if *p < 1e-3 {
+                *h = 0.0;
+                *p = 0.0;
+            }
+        }
+        self.energy *= k;
+        self.pulse *= k;
+        for f in &mut self.bar_flash {
+            *f *= k;
+        }
+    }
+
+    /// Exponentially fades the onset pulse; `decay` is the time constant in seconds.
+    fn decay_pulse(&mut self, dt: f32, decay: f32) {
+        self.pulse *= (-dt / decay.max(0.001)).exp();
+    }
+
+    fn decay_flashes(&mut self, dt: f32) {
+        let k = (-dt / TRANSIENT_FLASH_DECAY).exp();
+        for f in &mut self.bar_flash {
+            *f *= k;
+        }
     }
 
     fn tick_cooldowns(&mut self) {
@@ -273,9 +1376,8 @@ impl VisualizerState {
     }
 
     fn decay_peaks(&mut self) {
-        const BASS_LOW_BARS: usize = 10;
         for (i, p) in self.peak_heights.iter_mut().enumerate() {
-            let decay = if i < BASS_LOW_BARS {
+            let decay = if i < self.bands.bass_bars {
                 0.985
             } else {
                 0.98
@@ -285,30 +1387,209 @@ impl VisualizerState {
     }
 }
 
-#[macroquad::main("Audio Visualizer")]
-async fn main() {
-    let (tx, rx) = mpsc::channel::<Vec<f32>>();
-    let frames_received = Arc::new(AtomicU64::new(0));
+fn window_conf() -> Conf {
+    let config = Config::from_args();
+    Conf {
+        window_title: "Audio Visualizer".to_owned(),
+        window_width: config.width as i32,
+        window_height: config.height as i32,
+        fullscreen: config.fullscreen,
+        window_resizable: true,
+        ..Default::default()
+    }
+}
 
-    thread::spawn({
-        let frames = Arc::clone(&frames_received);
-        move || capture_audio(tx, frames)
-    });
+/// Windows builds use the GUI subsystem, so they start without a console and anything
+/// printed is lost. Attaching to the launching terminal's console, if there is one,
+/// makes `--help`, option errors and the capture diagnostics visible there.
+#[cfg(windows)]
+fn attach_parent_console() {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+    // Fails harmlessly when there's no parent console, e.g. when started from Explorer
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
 
-    let mut state = VisualizerState::new();
-    let mut show_fps = true;
-    let mut projectiles: Vec<Projectile> = Vec::new();
-    let mut fullscreen = false;
-    let mut saved_window_size: Option<(f32, f32)> = None;
-    let mut rainbow_phase: f32 = 0.0;
-    let mut rotating = false;
-    let mut circle_rotation: f32 = 0.0;
-    let mut rotation_speed: f32 = 0.0;
-    let mut prev_screen_size: (f32, f32) = (screen_width(), screen_height());
-    let mut projectile_decel_mode = false;
-    let mut projectile_distance_based = false;
-    let mut game_time: f32 = 0.0;
-    let mut last_input_peak: f32 = 0.0;
+fn main() {
+    #[cfg(windows)]
+    attach_parent_console();
+    // The benchmark and server run headless, so they have to be handled before macroquad opens a window
+    let config = Config::from_args();
+    if let Some(updates) = config.bench_fft {
+        bench_fft(config, updates);
+        return;
+    }
+    if let Some(port) = config.serve {
+        serve_audio(config, port);
+        return;
+    }
+    if config.print_bands {
+        print_bands(config);
+        return;
+    }
+    macroquad::Window::from_config(window_conf(), run());
+}
+
+/// Prints each bar's frequency span and FFT bins for the configured layout, as `update` uses them.
+fn print_bands(config: &Config) {
+    let state = VisualizerState::from_config(config);
+    let freq_per_bin = state.sample_rate / FFT_SIZE as f32;
+    println!(
+        "{} bars at {} Hz, {}-point FFT ({:.2} Hz per bin)",
+        BAR_COUNT, state.sample_rate, FFT_SIZE, freq_per_bin
+    );
+    println!("{:>4}  {:>10}  {:>10}  {:>10}  {:>11}", "bar", "start Hz", "center Hz", "end Hz", "bins");
+    for i in 0..BAR_COUNT {
+        let (f_start, f_end) = state.band_range(i);
+        let (start, end) = state.band_bins(i);
+        println!(
+            "{:>4}  {:>10.1}  {:>10.1}  {:>10.1}  {:>11}",
+            i,
+            f_start,
+            (f_start + f_end) / 2.0,
+            f_end,
+            format!("{}..{}", start, end)
+        );
+    }
+}
+
+/// Times `VisualizerState::update` on noise and prints its throughput.
+fn bench_fft(config: &Config, updates: usize) {
+    const CHUNKS: usize = 16;
+    let chunks: Vec<Vec<f32>> = (0..CHUNKS)
+        .map(|_| (0..FFT_SIZE).map(|_| rand::gen_range(-1.0, 1.0)).collect())
+        .collect();
+    let mut state = VisualizerState::from_config(config);
+    // Warm up so the AGC history and spectrum average are at their steady-state size
+    for chunk in chunks.iter().cycle().take(AGC_HISTORY_LEN) {
+        state.update(chunk);
+    }
+    let start = std::time::Instant::now();
+    for chunk in chunks.iter().cycle().take(updates) {
+        state.update(chunk);
+        // Projectiles are never spawned here; keep the fired list from growing unbounded
+        state.peak_fired.clear();
+    }
+    let elapsed = start.elapsed();
+    let ns_per_update = elapsed.as_nanos() as f64 / updates as f64;
+    println!(
+        "{} updates of {} samples in {:.3} s: {:.0} updates/s, {:.0} ns/update",
+        updates,
+        FFT_SIZE,
+        elapsed.as_secs_f64(),
+        1e9 / ns_per_update,
+        ns_per_update
+    );
+}
+
+async fn run() {
+    let config = Config::from_args();
+    if let Some((x, y)) = config.position {
+        macroquad::miniquad::window::set_window_position(x, y);
+    }
+    let (tx, rx) = mpsc::channel::<Vec<f32>>();
+    let frames_received = Arc::new(AtomicU64::new(0));
+    // Sample rate negotiated by the capture backend (0 until known)
+    let capture_rate = Arc::new(AtomicU32::new(0));
+    // Stereo phase correlation of the latest chunk as f32 bits; NaN when unknown (mono or silence)
+    let correlation = Arc::new(AtomicU32::new(f32::NAN.to_bits()));
+
+    spawn_audio_source(config, tx, &frames_received, &capture_rate, &correlation);
+
+    let mut state = VisualizerState::from_config(config);
+    let mut show_fps = true;
+    let mut projectiles: Vec<Projectile> = Vec::new();
+    let mut fullscreen = config.fullscreen;
+    let mut saved_window_size: Option<(f32, f32)> =
+        fullscreen.then_some((config.width as f32, config.height as f32));
+    let mut rainbow_phase: f32 = 0.0;
+    let mut rotating = config.rotate.is_some() || config.auto_rotate;
+    let mut circle_rotation: f32 = 0.0;
+    // Selected speed level; the ring actually turns at the eased `applied_rotation_speed`
+    let mut rotation_speed: f32 = config
+        .rotate
+        .map_or(0.0, |level| SPEED_LEVELS[(SPEED_LEVELS.len() as i32 / 2 + level) as usize]);
+    let mut applied_rotation_speed: f32 = 0.0;
+    let mut auto_rotate = config.auto_rotate;
+    // Auto-rotate spins this way; taken from the selected level until Up/Down picks one
+    let mut auto_direction: f32 = if rotation_speed < 0.0 { -1.0 } else { 1.0 };
+    let mut auto_energy: f32 = 0.0;
+    let mut beat_align = config.beat_align;
+    // Seconds left of the current beat's pull toward alignment
+    let mut beat_align_left: f32 = 0.0;
+    let mut prev_screen_size: (f32, f32) = (screen_width(), screen_height());
+    let mut projectile_decel_mode = config.projectile_decel;
+    let mut projectile_distance_based = config.projectile_distance_based;
+    let mut game_time: f32 = 0.0;
+    let mut last_input_peak: f32 = 0.0;
+    // Seconds since the last chunk above SILENCE_PEAK (or since any chunk arrived at all)
+    let mut silent_for: f32 = 0.0;
+    // Smoothed input normalization gain; `None` until the first non-silent chunk
+    let mut input_gain: Option<f32> = None;
+    let mut show_help = false;
+    let mut overlay_anchor = config.overlay;
+    let mut show_average = false;
+    let mut track_peak = false;
+    let mut show_note = false;
+    // Smoothed dominant frequency behind the note readout
+    let mut note_freq: Option<f32> = None;
+    let mut tracked_bar: usize = 0;
+    let mut show_correlation = false;
+    let mut bar_colors = config.bar_colors;
+    let palettes: Vec<Vec<Color>> = config
+        .palettes
+        .iter()
+        .map(|p| p.colors.iter().map(|&[r, g, b]| Color::from_rgba(r, g, b, 255)).collect())
+        .collect();
+    let mut mirror_projectiles = false;
+    let mut gravity_well = config.gravity_well;
+    let mut projectile_shape = config.projectile_shape;
+    // One knob over spawn limits, trail length and hue speed; 1.0 = the configured values
+    let mut intensity: f32 = config.intensity;
+    // Smoothed for display; per-chunk values jump around too much to read
+    let mut correlation_display: Option<f32> = None;
+    let mut db_scale = false;
+    let mut show_db_grid = false;
+    let mut projectile_color_mode = config.projectile_colors;
+    let mut bars_inward = config.bars_inward;
+    let mut render_mode = config.mode;
+    let mut beats_only = config.fire_on == FireOn::Beats;
+    let peak_color = config.peak_color.map(|[r, g, b]| Color::from_rgba(r, g, b, 255));
+    let [r, g, b] = config.circle_color;
+    let circle_color = Color::from_rgba(r, g, b, 255);
+    let mut reactive_circle = config.reactive_circle;
+    let mut vignette = config.vignette;
+    // Smoothed loudness (0..1) the vignette opens up with
+    let mut vignette_level: f32 = 0.0;
+    let mut inset = config.inset;
+    // Latest normalized chunk, kept for the waveform inset
+    let mut last_chunk: Vec<f32> = Vec::new();
+    // Smoothed loudness driving the reactive circle: (bass, overall), each 0..1
+    let mut circle_level: (f32, f32) = (0.0, 0.0);
+    // Firing thresholds are shown briefly after they're adjusted
+    let mut show_thresholds_until: f32 = 0.0;
+    // Bars shorter than this fraction are drawn with zero length; firing still sees them
+    let mut dead_zone = config.dead_zone;
+    let mut bar_gap = config.bar_gap;
+    let mut reactive_radius = config.reactive_radius.map_or(INNER_RADIUS, |(min_r, _)| min_r);
+    // Only the settings panel changes these after startup
+    #[cfg_attr(not(feature = "settings-panel"), allow(unused_mut))]
+    let mut gain = config.gain;
+    #[cfg_attr(not(feature = "settings-panel"), allow(unused_mut))]
+    let mut color_gamma = config.color_gamma;
+    #[cfg_attr(not(feature = "settings-panel"), allow(unused_mut))]
+    let mut max_spawns_per_frame = config.max_spawns_per_frame;
+    #[cfg_attr(not(feature = "settings-panel"), allow(unused_mut))]
+    let mut beat_burst = config.beat_burst;
+    #[cfg(feature = "settings-panel")]
+    let mut show_settings = false;
+    // When the previous frame was handed to the display, for --max-fps
+    let mut frame_start = std::time::Instant::now();
 
     loop {
         let (w, h) = (screen_width(), screen_height());
@@ -336,12 +1617,24 @@ async fn main() {
             }
             fullscreen = false;
         }
+        let shift = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+        if is_key_pressed(KeyCode::H) || is_key_pressed(KeyCode::F1) || (shift && is_key_pressed(KeyCode::Slash)) {
+            show_help = !show_help;
+        }
         if is_key_pressed(KeyCode::Space) {
             show_fps = !show_fps;
         }
+        #[cfg(feature = "settings-panel")]
+        if is_key_pressed(KeyCode::F2) {
+            show_settings = !show_settings;
+        }
+        if is_key_pressed(KeyCode::O) {
+            overlay_anchor = overlay_anchor.next();
+        }
         if is_key_pressed(KeyCode::S) {
             if rotating {
                 circle_rotation = 0.0;
+                applied_rotation_speed = 0.0;
             }
             rotating = !rotating;
         }
@@ -351,7 +1644,124 @@ async fn main() {
         if is_key_pressed(KeyCode::D) {
             projectile_distance_based = !projectile_distance_based;
         }
-        if rotating {
+        if is_key_pressed(KeyCode::C) {
+            projectile_color_mode = projectile_color_mode.next();
+        }
+        if is_key_pressed(KeyCode::P) {
+            bar_colors = bar_colors.next(palettes.len());
+        }
+        if is_key_pressed(KeyCode::X) {
+            mirror_projectiles = !mirror_projectiles;
+        }
+        if is_key_pressed(KeyCode::Z) {
+            projectile_shape = projectile_shape.next();
+        }
+        if is_key_pressed(KeyCode::J) {
+            gravity_well = !gravity_well;
+        }
+        if is_key_pressed(KeyCode::F) {
+            beat_align = !beat_align;
+        }
+        if is_key_pressed(KeyCode::Q) {
+            auto_rotate = !auto_rotate;
+            rotating |= auto_rotate;
+        }
+        if is_key_pressed(KeyCode::Tab) {
+            vignette = !vignette;
+        }
+        if is_key_pressed(KeyCode::U) {
+            reactive_circle = !reactive_circle;
+        }
+        if is_key_pressed(KeyCode::Comma) {
+            intensity = (intensity - INTENSITY_STEP).max(MIN_INTENSITY);
+        }
+        if is_key_pressed(KeyCode::Period) {
+            intensity = (intensity + INTENSITY_STEP).min(MAX_INTENSITY);
+        }
+        if is_key_pressed(KeyCode::LeftBracket) {
+            state.nudge_vocal_offset(-1);
+        }
+        if is_key_pressed(KeyCode::RightBracket) {
+            state.nudge_vocal_offset(1);
+        }
+        let threshold_step = if is_key_pressed(KeyCode::Equal) {
+            0.005
+        } else if is_key_pressed(KeyCode::Minus) {
+            -0.005
+        } else {
+            0.0
+        };
+        if threshold_step != 0.0 {
+            if shift {
+                state.peak_hysteresis = (state.peak_hysteresis + threshold_step).clamp(0.0, 0.5);
+            } else {
+                state.transient_threshold = (state.transient_threshold + threshold_step).clamp(0.0, 0.5);
+            }
+            show_thresholds_until = game_time + 2.0;
+        }
+        if is_key_pressed(KeyCode::Y) {
+            let step = if shift { -DEAD_ZONE_STEP } else { DEAD_ZONE_STEP };
+            dead_zone = (dead_zone + step).clamp(0.0, MAX_DEAD_ZONE);
+            show_thresholds_until = game_time + 2.0;
+        }
+        if is_key_pressed(KeyCode::Semicolon) {
+            let step = if shift { -BAR_GAP_STEP } else { BAR_GAP_STEP };
+            bar_gap = (bar_gap + step).clamp(0.0, MAX_BAR_GAP);
+        }
+        if is_key_pressed(KeyCode::V) {
+            render_mode = render_mode.next();
+            projectiles.clear();
+        }
+        if is_key_pressed(KeyCode::I) {
+            bars_inward = !bars_inward;
+        }
+        if is_key_pressed(KeyCode::B) {
+            beats_only = !beats_only;
+        }
+        if is_key_pressed(KeyCode::M) {
+            show_average = !show_average;
+        }
+        if is_key_pressed(KeyCode::N) {
+            state.set_average_overlay(!state.average_overlay, config.spectrum_average);
+        }
+        if is_key_pressed(KeyCode::R) {
+            state.reset_average();
+        }
+        if is_key_pressed(KeyCode::T) {
+            track_peak = !track_peak;
+        }
+        if is_key_pressed(KeyCode::Backslash) {
+            show_note = !show_note;
+        }
+        if is_key_pressed(KeyCode::Key1) {
+            inset = inset.next();
+        }
+        if is_key_pressed(KeyCode::W) {
+            show_correlation = !show_correlation;
+        }
+        if is_key_pressed(KeyCode::E) {
+            match export_spectrum_csv(&state.bar_centers, &state.bar_heights) {
+                Ok(path) => eprintln!("Spectrum saved to {}", path.display()),
+                Err(e) => eprintln!("Warning: could not save spectrum: {}", e),
+            }
+        }
+        if is_key_pressed(KeyCode::K) {
+            state.spectral_smoothing = (state.spectral_smoothing + 1) % (MAX_SPECTRAL_SMOOTHING + 1);
+        }
+        if is_key_pressed(KeyCode::L) {
+            db_scale = !db_scale;
+        }
+        if is_key_pressed(KeyCode::G) {
+            show_db_grid = !show_db_grid;
+        }
+        if rotating && auto_rotate {
+            if is_key_pressed(KeyCode::Up) {
+                auto_direction = 1.0;
+            }
+            if is_key_pressed(KeyCode::Down) {
+                auto_direction = -1.0;
+            }
+        } else if rotating {
             if is_key_pressed(KeyCode::Up) {
                 rotation_speed = next_speed(rotation_speed, 1);
             }
@@ -360,63 +1770,215 @@ async fn main() {
             }
         }
 
-        while let Ok(mut data) = rx.try_recv() {
+        state.set_sample_rate(capture_rate.load(Ordering::Relaxed));
+        let stopped = config.silence_timeout.is_some_and(|timeout| silent_for >= timeout);
+        silent_for += get_frame_time();
+        // After a hitch the channel can hold a long backlog, but only the newest chunks shape what's
+        // drawn. Skipping the rest trades a little temporal accuracy (their onsets and peaks are
+        // lost) for not running a burst of FFTs on the frame that's already late.
+        let mut pending: Vec<Vec<f32>> = rx.try_iter().collect();
+        if pending.len() > MAX_CHUNKS_PER_FRAME {
+            pending.drain(..pending.len() - MAX_CHUNKS_PER_FRAME);
+        }
+        for mut data in pending {
             // Normalize level so low tap output (e.g. macOS aggregate) still shows bars
             let peak = data
                 .iter()
                 .map(|&s| s.abs())
                 .fold(0.0f32, f32::max);
+            if peak >= SILENCE_PEAK {
+                silent_for = 0.0;
+            } else if stopped || silent_for < config.dropout_hold {
+                // A silent chunk right after audio is most likely a capture hiccup padded with
+                // zeros; holding the bars hides the dip. Once stopped, normalization would blow the
+                // noise floor back up into visible bars.
+                last_input_peak = peak;
+                continue;
+            }
             if peak > 1e-8 {
                 let target = 0.4f32;
-                let scale = (target / peak).min(1000.0);
+                let chunk_scale = (target / peak).min(1000.0);
+                let scale = match input_gain {
+                    Some(g) => {
+                        let rate = if chunk_scale < g { INPUT_GAIN_ATTACK } else { INPUT_GAIN_RELEASE };
+                        g + (chunk_scale - g) * rate
+                    }
+                    None => chunk_scale,
+                };
                 for s in &mut data {
-                    *s *= scale;
+                    *s *= scale * gain;
                 }
+                input_gain = Some(scale);
             }
             last_input_peak = peak;
+            if inset == Inset::Waveform {
+                last_chunk.clone_from(&data);
+            }
             state.update(&data);
         }
+        if stopped {
+            state.fade_out(get_frame_time(), SILENCE_FADE);
+            projectiles.clear();
+        }
         state.tick_cooldowns();
         state.decay_peaks();
+        state.decay_pulse(get_frame_time(), config.pulse_decay);
+        state.decay_flashes(get_frame_time());
+        let pulse = if config.pulse { state.pulse * PULSE_BRIGHTNESS } else { 0.0 };
 
         let screen_width = screen_width();
         let screen_height = screen_height();
+        let ui = ui_scale(config.ui_scale, screen_height);
+        let half_circle = render_mode == RenderMode::HalfCircle;
         let cx = screen_width / 2.0;
-        let cy = screen_height / 2.0;
-        let inner_radius = 40.0;
-        let max_bar_length = (screen_width.min(screen_height) * 0.5) - inner_radius;
-        let angle_step = std::f32::consts::TAU / BAR_COUNT as f32;
-        let gap = angle_step * 0.04;
+        let cy = if half_circle {
+            screen_height - HALF_CIRCLE_MARGIN
+        } else {
+            screen_height / 2.0
+        };
+        let inner_radius = match config.reactive_radius {
+            Some((min_r, max_r)) => {
+                let target = min_r + (max_r - min_r) * (state.energy * 2.0).min(1.0);
+                reactive_radius += (target - reactive_radius) * (1.0 - (-8.0 * get_frame_time()).exp());
+                reactive_radius
+            }
+            None => INNER_RADIUS,
+        };
+        let available = if half_circle {
+            (screen_width * 0.5).min(screen_height - HALF_CIRCLE_MARGIN)
+        } else {
+            screen_width.min(screen_height) * 0.5
+        };
+        let ring_fits = available > inner_radius;
+        let max_bar_length = (available - inner_radius).max(0.0);
+        // Circle the bars grow from: the inner circle, or the outer edge when growing inward
+        let (anchor_radius, bar_dir) = if bars_inward {
+            (inner_radius + max_bar_length, -1.0)
+        } else {
+            (inner_radius, 1.0)
+        };
+        auto_energy += (state.energy - auto_energy) * (1.0 - (-get_frame_time() / AUTO_ROTATE_SMOOTHING).exp());
+        let winding = if config.counter_clockwise { -1.0 } else { 1.0 };
+        let layout_start = config.start_angle.to_radians();
+        if beat_align && state.beat {
+            beat_align_left = BEAT_ALIGN_TIME;
+        }
         if rotating {
-            circle_rotation += rotation_speed * get_frame_time();
+            let dt = get_frame_time();
+            let target_speed = if auto_rotate {
+                let max_speed = SPEED_LEVELS[SPEED_LEVELS.len() - 1];
+                auto_direction * max_speed * (auto_energy * 2.0).min(1.0)
+            } else {
+                rotation_speed
+            };
+            applied_rotation_speed +=
+                (target_speed - applied_rotation_speed) * (1.0 - (-dt / ROTATION_EASE).exp());
+            circle_rotation += applied_rotation_speed * dt;
+            if beat_align_left > 0.0 && !half_circle {
+                // Pull the reference bar toward straight up the short way round; the spin carries on
+                beat_align_left -= dt;
+                let bar_angle = layout_start
+                    + circle_rotation
+                    + (config.beat_align_bar as f32 + 0.5) * winding * std::f32::consts::TAU / BAR_COUNT as f32;
+                let error = (-std::f32::consts::FRAC_PI_2 - bar_angle + std::f32::consts::PI)
+                    .rem_euclid(std::f32::consts::TAU)
+                    - std::f32::consts::PI;
+                circle_rotation += error * (1.0 - (-dt / BEAT_ALIGN_EASE).exp());
+            }
         }
-        let rotation = std::f32::consts::FRAC_PI_4 + circle_rotation;
+        // Angle of bar 0's leading edge and the (signed) angle each bar covers. The half
+        // circle sweeps over the top and ignores rotation and start angle so it stays
+        // anchored; counter-clockwise winding runs it right to left instead.
+        let (arc_start, angle_step) = if half_circle {
+            (
+                if config.counter_clockwise { 0.0 } else { -std::f32::consts::PI },
+                winding * std::f32::consts::PI / BAR_COUNT as f32,
+            )
+        } else {
+            (layout_start + circle_rotation, winding * std::f32::consts::TAU / BAR_COUNT as f32)
+        };
+        let gap = angle_step * bar_gap.min(MAX_BAR_GAP);
 
         let dt = get_frame_time();
         game_time += dt;
 
+        // Mirrored twins count against the spawn limits too
+        let per_band = if mirror_projectiles { 2 } else { 1 };
+        let spawn_cap = max_spawns_per_frame.map(|n| (n as f32 * intensity).round() as usize);
+        let burst_size = (beat_burst as f32 * intensity).round() as usize;
+        let trail_len = ((TRAIL_LEN as f32 * intensity).round() as usize).clamp(2, MAX_TRAIL_LEN);
+        let beat = std::mem::take(&mut state.beat);
+        // Beat-synced hue: step once per beat and hold in between
+        if let (Some(step), true) = (config.beat_hue_step, beat) {
+            rainbow_phase = (rainbow_phase + step).rem_euclid(360.0);
+        }
+        if beats_only {
+            // Ignore per-bar peaks; a beat launches a burst from the loudest bars instead
+            state.peak_fired.clear();
+            if beat {
+                let heights = &state.bar_heights;
+                let mut loudest: Vec<usize> = (0..BAR_COUNT).collect();
+                loudest.sort_by(|&a, &b| heights[b].total_cmp(&heights[a]));
+                loudest.truncate((burst_size / per_band).max(1));
+                state.peak_fired = loudest;
+            }
+        } else if let Some(spawn_cap) = spawn_cap.filter(|&n| state.peak_fired.len() * per_band > n) {
+            // Global spawn cap: keep only the loudest firing bands this frame
+            let peaks = &state.peak_heights;
+            state.peak_fired.sort_by(|&a, &b| peaks[b].total_cmp(&peaks[a]));
+            state.peak_fired.truncate((spawn_cap / per_band).max(1));
+        }
+        // Display-only: firing, peaks and the CSV export keep the unsmoothed bars
+        let shown_heights = smooth_bars(&state.bar_heights, config.bar_smoothing, config.bar_smoothing_wrap && !half_circle);
         for bar_i in state.peak_fired.drain(..) {
-            let peak_length = state.peak_heights[bar_i] * max_bar_length;
+            let peak_length = height_to_fraction(state.peak_heights[bar_i], db_scale) * max_bar_length;
             if peak_length > 3.0 {
-                let mid_angle = -std::f32::consts::FRAC_PI_2 + rotation + (bar_i as f32 + 0.5) * angle_step;
+                let mid_angle = arc_start + (bar_i as f32 + 0.5) * angle_step;
                 let dx = mid_angle.cos();
                 let dy = mid_angle.sin();
+                // In the gravity well, launch mostly sideways so projectiles carry angular momentum
+                let launch_angle = if gravity_well { mid_angle + winding * ORBIT_LAUNCH_ANGLE } else { mid_angle };
                 let peak = state.peak_heights[bar_i];
                 let size = (1.0 + peak * 7.0).clamp(1.0, 8.0);
-                projectiles.push(Projectile {
-                    x: cx + inner_radius * dx,
-                    y: cy + inner_radius * dy,
-                    dx: dx * 180.0,
-                    dy: dy * 180.0,
-                    hue: rainbow_phase,
+                let launch_radius = match config.launch_from {
+                    LaunchOrigin::Base => anchor_radius,
+                    LaunchOrigin::Tip => (anchor_radius + bar_dir * peak_length).max(0.0),
+                };
+                // Match the bar as drawn: smoothed, and blank inside the dead zone
+                let bar_hue = || {
+                    let shown = shown_heights[bar_i];
+                    let height = if shown < dead_zone { 0.0 } else { shown };
+                    let rise = state.bar_deltas[bar_i] * config.rise_sensitivity;
+                    color_hue(bar_color(bar_colors, &palettes, height, rise, color_gamma))
+                };
+                let projectile = Projectile {
+                    x: cx + launch_radius * dx,
+                    y: cy + launch_radius * dy,
+                    dx: launch_angle.cos() * 180.0,
+                    dy: launch_angle.sin() * 180.0,
+                    hue: projectile_color_mode.hue(bar_i, rainbow_phase, bar_hue),
                     size,
-                    trail: Vec::new(),
+                    trail: VecDeque::with_capacity(MAX_TRAIL_LEN + 1),
                     birth_time: game_time,
-                });
-                rainbow_phase = (rainbow_phase + 3.0) % 360.0;
+                };
+                // Reflect across the screen's vertical center line, whatever the ring's rotation
+                if mirror_projectiles {
+                    projectiles.push(Projectile {
+                        x: 2.0 * cx - projectile.x,
+                        dx: -projectile.dx,
+                        trail: VecDeque::with_capacity(MAX_TRAIL_LEN + 1),
+                        ..projectile
+                    });
+                }
+                projectiles.push(projectile);
+                if config.beat_hue_step.is_none() {
+                    rainbow_phase = (rainbow_phase + 3.0 * intensity) % 360.0;
+                }
             }
         }
-        rainbow_phase = (rainbow_phase + 1.5) % 360.0;
+        if config.beat_hue_step.is_none() {
+            rainbow_phase = (rainbow_phase + 1.5 * intensity) % 360.0;
+        }
 
         // Distance mode: slowdown only in this many px before edge; curve keeps min speed until closer
         const PROXIMITY_RANGE: f32 = 200.0;
@@ -424,12 +1986,33 @@ async fn main() {
         // Time mode: seconds alive before proximity reaches 1 (min speed); longer = stay fast until nearer edge
         const TIME_RAMP: f32 = 6.0;
         const SPEED_RATE: f32 = 2.5;
-        const MIN_SPEED: f32 = 90.0;
         let margin = 80.0;
+        // Orbits never leave the screen, so the well always imposes a lifetime of its own
+        let lifetime = if gravity_well {
+            Some(config.max_lifetime.map_or(ORBIT_LIFETIME, |l| l.min(ORBIT_LIFETIME)))
+        } else {
+            config.max_lifetime
+        };
         projectiles.retain_mut(|p| {
-            p.trail.push((p.x, p.y));
-            if p.trail.len() > 12 {
-                p.trail.remove(0);
+            if lifetime.is_some_and(|l| game_time - p.birth_time >= l) {
+                return false;
+            }
+            p.trail.push_back((p.x, p.y));
+            while p.trail.len() > trail_len {
+                p.trail.pop_front();
+            }
+            if gravity_well {
+                let (rx, ry) = (cx - p.x, cy - p.y);
+                let dist_sq = (rx * rx + ry * ry).max(1.0);
+                // Acceleration G / r along the unit vector (rx, ry) / r
+                let pull = ORBIT_STRENGTH / dist_sq * dt;
+                let drag = (-ORBIT_DRAG * dt).exp();
+                p.dx = (p.dx + rx * pull) * drag;
+                p.dy = (p.dy + ry * pull) * drag;
+                p.x += p.dx * dt;
+                p.y += p.dy * dt;
+                // Retire projectiles that fell into the well
+                return dist_sq >= (anchor_radius * 0.5).powi(2);
             }
             let proximity = if projectile_distance_based {
                 let speed = (p.dx * p.dx + p.dy * p.dy).sqrt();
@@ -475,16 +2058,9 @@ async fn main() {
                 (time_alive / TIME_RAMP).min(1.0)
             };
             let rate = SPEED_RATE * proximity * dt;
-            let speed_mult = if projectile_decel_mode {
-                1.0 / (1.0 + rate)
-            } else {
-                1.0 + rate
-            };
-            p.dx *= speed_mult;
-            p.dy *= speed_mult;
-            let new_speed = (p.dx * p.dx + p.dy * p.dy).sqrt();
-            if new_speed > 0.0 && new_speed < MIN_SPEED {
-                let scale = MIN_SPEED / new_speed;
+            let speed = (p.dx * p.dx + p.dy * p.dy).sqrt();
+            if speed > 0.0 {
+                let scale = projectile_speed(speed, rate, projectile_decel_mode, config.min_speed) / speed;
                 p.dx *= scale;
                 p.dy *= scale;
             }
@@ -501,167 +2077,1346 @@ async fn main() {
 
         clear_background(BLACK);
 
-        let perspective_ref = (screen_width.max(screen_height) * 0.55).max(400.0);
-        for p in projectiles.iter() {
-            let dist = ((p.x - cx).powi(2) + (p.y - cy).powi(2)).sqrt();
-            let perspective = 0.5 + 1.2 * (dist / perspective_ref).min(1.0);
-            let len = p.trail.len() as f32;
-            for (i, &(tx, ty)) in p.trail.iter().enumerate() {
-                let t = i as f32 / len.max(1.0);
-                let alpha = 0.04 + 0.7 * t * t;
-                let mut c = hsv_to_color(p.hue, 0.9, 1.0);
-                c.a = alpha;
-                let trail_dist = ((tx - cx).powi(2) + (ty - cy).powi(2)).sqrt();
-                let trail_perspective = 0.5 + 1.2 * (trail_dist / perspective_ref).min(1.0);
-                let trail_size = p.size * (0.3 + 0.7 * t) * trail_perspective;
-                let half = trail_size / 2.0;
-                draw_rectangle(tx - half, ty - half, trail_size, trail_size, c);
-            }
-            let c = hsv_to_color(p.hue, 0.95, 1.0);
-            let head_size = p.size * perspective;
-            let half = head_size / 2.0;
-            draw_rectangle(p.x - half, p.y - half, head_size, head_size, c);
-        }
-
-        for (i, &height) in state.bar_heights.iter().enumerate() {
-            let bar_length = height * max_bar_length;
-            let start_angle = -std::f32::consts::FRAC_PI_2 + rotation + i as f32 * angle_step + gap;
-            let end_angle = -std::f32::consts::FRAC_PI_2 + rotation + (i + 1) as f32 * angle_step - gap;
-
-            let color_index = (height * (COLORS.len() - 1) as f32) as usize;
-            let color = COLORS[color_index.min(COLORS.len() - 1)];
-
-            let outer_radius = inner_radius + bar_length;
-
-            let v1 = Vec2::new(cx + inner_radius * start_angle.cos(), cy + inner_radius * start_angle.sin());
-            let v2 = Vec2::new(cx + outer_radius * start_angle.cos(), cy + outer_radius * start_angle.sin());
-            let v3 = Vec2::new(cx + outer_radius * end_angle.cos(), cy + outer_radius * end_angle.sin());
-            let v4 = Vec2::new(cx + inner_radius * end_angle.cos(), cy + inner_radius * end_angle.sin());
-
-            draw_triangle(v1, v2, v3, color);
-            draw_triangle(v1, v3, v4, color);
-
-            let peak_length = state.peak_heights[i] * max_bar_length;
-            if peak_length > 3.0 {
-                let peak_radius = inner_radius + peak_length;
-                let mid_angle = start_angle + angle_step * 0.5;
-                let px = cx + peak_radius * mid_angle.cos();
-                let py = cy + peak_radius * mid_angle.sin();
-                let perp_x = -mid_angle.sin() * 4.0;
-                let perp_y = mid_angle.cos() * 4.0;
-                draw_line(px - perp_x, py - perp_y, px + perp_x, py + perp_y, 2.0, WHITE);
+        let ring = RingGeometry {
+            center: vec2(cx, cy),
+            anchor_radius,
+            max_bar_length,
+            bar_dir,
+            arc_start,
+            angle_step,
+            gap,
+        };
+        if show_db_grid {
+            draw_db_grid(&ring, db_scale, ui);
+        }
+
+        // Fades to nothing over the last LIFETIME_FADE seconds before removal
+        let fade = |p: &Projectile| {
+            lifetime.map_or(1.0, |l| ((l - (game_time - p.birth_time)) / LIFETIME_FADE).clamp(0.0, 1.0))
+        };
+        let screen = Rect::new(0.0, 0.0, screen_width, screen_height);
+        draw_projectiles(&projectiles, screen, ring.center, pulse, projectile_shape, config, fade);
+
+        // Too small a window to fit the inner circle: skip the ring rather than draw it inverted
+        if ring_fits {
+            let theme = BarTheme {
+                bar_colors,
+                palettes: &palettes,
+                color_gamma,
+                db_scale,
+                dead_zone,
+                pulse,
+                peak_color,
+            };
+            if config.second_ring != SecondRing::Off {
+                let heights = match config.second_ring {
+                    SecondRing::Peaks => &state.peak_heights,
+                    _ => &state.bar_heights,
+                };
+                let ring_start = if config.second_ring_reverse && !half_circle {
+                    layout_start - circle_rotation
+                } else {
+                    arc_start
+                };
+                draw_second_ring(&ring, ring_start, config.second_ring_radius, heights, &theme);
+            }
+
+            if show_average {
+                draw_bar_ghosts(&ring, &state.average_heights, db_scale, Color::new(1.0, 1.0, 1.0, 0.18));
+            }
+
+            draw_bar_ring(&ring, &shown_heights, &state, &theme, config);
+
+            if state.average_overlay {
+                // The full ring closes back onto the first bar; the half-circle stays open
+                draw_bar_contour(&ring, &state.averaged_heights, db_scale, !half_circle);
+            }
+
+            if track_peak {
+                // Only hop to a new loudest bar when it clearly beats the tracked one, so near-ties don't flicker
+                let heights = &shown_heights;
+                let loudest = (0..BAR_COUNT).max_by(|&a, &b| heights[a].total_cmp(&heights[b])).unwrap_or(0);
+                if heights[loudest] > heights[tracked_bar] * TRACKER_HYSTERESIS {
+                    tracked_bar = loudest;
+                }
+                let height = heights[tracked_bar];
+                if height > 0.01 {
+                    let tip_radius = ring.tip_radius(height_to_fraction(height, db_scale) * max_bar_length);
+                    let rise = state.bar_deltas[tracked_bar] * config.rise_sensitivity;
+                    let color = brighten(bar_color(bar_colors, &palettes, height, rise, color_gamma), 0.6);
+                    ring.wedge(arc_start, tracked_bar, anchor_radius, tip_radius, color);
+
+                    let mid_angle = ring.bar_angles(arc_start, tracked_bar).0 + angle_step * 0.5;
+                    let label_radius = (tip_radius + bar_dir * 14.0 * ui).max(0.0);
+                    draw_text(
+                        &format_freq(state.bar_centers[tracked_bar]),
+                        cx + label_radius * mid_angle.cos() - 20.0 * ui,
+                        cy + label_radius * mid_angle.sin(),
+                        18.0 * ui,
+                        WHITE,
+                    );
+                }
             }
+
+            let circle_color = if reactive_circle {
+                let bass_bars = state.bands.bass_bars;
+                let bass = state.bar_heights[..bass_bars].iter().sum::<f32>() / bass_bars as f32;
+                let k = 1.0 - (-6.0 * dt).exp();
+                circle_level.0 += (bass.min(1.0) - circle_level.0) * k;
+                circle_level.1 += ((state.energy * 2.0).min(1.0) - circle_level.1) * k;
+                // Bass swings the hue from blue toward red; overall loudness brightens it
+                let hue = GRADIENT_LOW_HUE * (1.0 - circle_level.0);
+                brighten(hsv_to_color(hue, 0.8, 0.45 + 0.55 * circle_level.1), pulse)
+            } else {
+                circle_color
+            };
+            draw_circle_lines(cx, cy, anchor_radius, config.circle_thickness, circle_color);
         }
 
-        draw_circle_lines(cx, cy, inner_radius, 2.0, GRAY);
+        // Quiet passages close the vignette in, loud ones open it up
+        vignette_level += ((state.energy * 2.0).min(1.0) - vignette_level) * (1.0 - (-dt / VIGNETTE_SMOOTHING).exp());
+        if vignette {
+            draw_vignette(screen_width, screen_height, VIGNETTE_MAX_ALPHA * (1.0 - vignette_level));
+        }
+
+        if inset != Inset::Off {
+            let rect = inset_rect(config.inset_corner, config.inset_size, screen_width, screen_height);
+            draw_rectangle(rect.x, rect.y, rect.w, rect.h, Color::new(0.0, 0.0, 0.0, 0.7));
+            match inset {
+                Inset::Spectrum => draw_spectrum(rect, &shown_heights, db_scale, |height| {
+                    bar_color(bar_colors, &palettes, height, 0.0, color_gamma)
+                }),
+                _ => draw_waveform(rect, &last_chunk, INSET_WAVE_COLOR),
+            }
+            draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 1.0, Color::new(0.5, 0.5, 0.5, 0.6));
+        }
 
         // Draw FPS
         if show_fps {
-            let y = 30.0;
-            draw_text(&format!("FPS: {:.0}", get_fps()), 10.0, y, 20.0, GREEN);
-            draw_text(
-                &format!("Audio: {} frames", frames_received.load(Ordering::Relaxed)),
-                10.0,
-                y + 25.0,
-                16.0,
-                if frames_received.load(Ordering::Relaxed) > 0 {
-                    GREEN
-                } else {
-                    RED
-                },
-            );
+            let frames = frames_received.load(Ordering::Relaxed);
+            let mut lines = vec![
+                (0.0, format!("FPS: {:.0}", get_fps()), 20.0, GREEN),
+                (25.0, format!("Audio: {} frames", frames), 16.0, if frames > 0 { GREEN } else { RED }),
+                (
+                    44.0,
+                    format!("Level: {:.4} (before norm)", last_input_peak),
+                    14.0,
+                    if last_input_peak > 1e-6 { GREEN } else { ORANGE },
+                ),
+                (63.0, "H / F1 / ?: Help".to_string(), 14.0, DARKGRAY),
+                (
+                    81.0,
+                    format!(
+                        "Projectile colors: {} | Firing: {} | Intensity: {:.2} | Vocal offset: {:+} | Smoothing: {}",
+                        projectile_color_mode.label(),
+                        firing_label(beats_only, state.bass_autofire),
+                        intensity,
+                        state.vocal_offset,
+                        state.spectral_smoothing
+                    ),
+                    14.0,
+                    DARKGRAY,
+                ),
+            ];
+            if frames == 0 {
+                lines.push((99.0, "No audio".to_string(), 12.0, ORANGE));
+            }
+            let rate = capture_rate.load(Ordering::Relaxed);
+            if rate != 0 && rate != SAMPLE_RATE {
+                lines.push((117.0, format!("Capture rate: {} Hz", rate), 14.0, GRAY));
+            }
+            draw_overlay_text(&lines, overlay_anchor, screen_width, screen_height, ui);
+        }
+        if show_correlation {
+            let latest = f32::from_bits(correlation.load(Ordering::Relaxed));
+            correlation_display = if latest.is_nan() {
+                None
+            } else {
+                Some(correlation_display.map_or(latest, |c| c + (latest - c) * 0.15))
+            };
+            // Top-right unless the status overlay already lives there
+            let y = if show_fps && overlay_anchor == OverlayAnchor::TopRight {
+                screen_height - 40.0 * ui
+            } else {
+                20.0 * ui
+            };
+            draw_correlation_meter(correlation_display, screen_width - 230.0 * ui, y, ui);
+        }
+        if game_time < show_thresholds_until {
+            // Stay clear of the status overlay when it sits in the same corner
+            let y = if show_fps && overlay_anchor == OverlayAnchor::BottomLeft {
+                30.0 * ui
+            } else {
+                screen_height - 12.0 * ui
+            };
             draw_text(
-                &format!("Level: {:.4} (before norm)", last_input_peak),
-                10.0,
-                y + 44.0,
-                14.0,
-                if last_input_peak > 1e-6 { GREEN } else { ORANGE },
+                &format!(
+                    "Transient: {:.3} | Peak hysteresis: {:.3} | Dead zone: {:.2}",
+                    state.transient_threshold, state.peak_hysteresis, dead_zone
+                ),
+                10.0 * ui,
+                y,
+                16.0 * ui,
+                YELLOW,
             );
-            draw_text("SPACE: FPS | S: Rotate | ↑↓: Speed | A: Accel/Decel | D: Dist/Time | F11: Fullscreen", 10.0, y + 63.0, 14.0, DARKGRAY);
-            if frames_received.load(Ordering::Relaxed) == 0 {
-                draw_text("No audio", 10.0, y + 99.0, 12.0, ORANGE);
+        }
+        if show_help {
+            let on_off = |on: bool| if on { "on" } else { "off" }.to_string();
+            draw_help_overlay(&[
+                (
+                    "Rotating",
+                    if rotating && auto_rotate {
+                        format!("auto, {:+.0}°/s", applied_rotation_speed.to_degrees())
+                    } else if rotating {
+                        format!("on, {:+.0}°/s", rotation_speed.to_degrees())
+                    } else {
+                        "off".into()
+                    },
+                ),
+                ("Beat align", on_off(beat_align)),
+                ("Projectiles", if projectile_decel_mode { "decel" } else { "accel" }.into()),
+                ("Slowdown", if projectile_distance_based { "distance" } else { "time" }.into()),
+                ("Firing", firing_label(beats_only, state.bass_autofire).into()),
+                ("Proj. colors", projectile_color_mode.label().into()),
+                ("Proj. shape", projectile_shape.label().into()),
+                (
+                    "Bar colors",
+                    match bar_colors {
+                        BarColors::Custom(i) => format!("custom: {}", config.palettes[i].name),
+                        _ => bar_colors.label().into(),
+                    },
+                ),
+                ("Layout", if half_circle { "half-circle" } else { "circle" }.into()),
+                ("Inward", on_off(bars_inward)),
+                ("dB scale", on_off(db_scale)),
+                ("dB grid", on_off(show_db_grid)),
+                ("Peak tracker", on_off(track_peak)),
+                ("Note readout", on_off(show_note)),
+                ("Correlation", on_off(show_correlation)),
+                ("Mirror", on_off(mirror_projectiles)),
+                ("Gravity well", on_off(gravity_well)),
+                ("Reactive circle", on_off(reactive_circle)),
+                ("Vignette", on_off(vignette)),
+                ("Inset", inset.label().into()),
+                ("Intensity", format!("{:.2}", intensity)),
+                ("Bar gap", format!("{:.0}%", bar_gap * 100.0)),
+                (
+                    "Average",
+                    format!("{}, {:.0} s", on_off(show_average), state.average_chunks as f32 * FFT_SIZE as f32 / state.sample_rate),
+                ),
+                (
+                    "Average contour",
+                    if state.average_overlay {
+                        format!("on, {} frames", state.spectrum_frames)
+                    } else {
+                        on_off(false)
+                    },
+                ),
+                ("Smoothing", state.spectral_smoothing.to_string()),
+                ("FPS display", on_off(show_fps)),
+                ("Overlay", overlay_anchor.label().into()),
+                ("Input gain", format!("{:.1}x", input_gain.unwrap_or(1.0))),
+            ], &config.palettes, screen_width, screen_height, ui);
+        }
+        #[cfg(feature = "settings-panel")]
+        if show_settings {
+            use config::ProjectileColorMode;
+            use macroquad::ui::{hash, root_ui, widgets};
+            const BAR_COLOR_MODES: [BarColors; 3] = [BarColors::Palette, BarColors::Gradient, BarColors::Rise];
+            const PROJECTILE_COLOR_MODES: [ProjectileColorMode; 4] = [
+                ProjectileColorMode::Rainbow,
+                ProjectileColorMode::Fixed,
+                ProjectileColorMode::Band,
+                ProjectileColorMode::Bar,
+            ];
+            let (mut attack, mut release) = (state.attack_time, state.release_time);
+            // Loaded palettes follow the built-in modes, as in the P cycle
+            let bar_color_names: Vec<&str> = ["palette", "gradient", "rise"]
+                .into_iter()
+                .chain(config.palettes.iter().map(|p| p.name.as_str()))
+                .collect();
+            let mut bar_colors_idx = match bar_colors {
+                BarColors::Custom(i) => BAR_COLOR_MODES.len() + i,
+                mode => BAR_COLOR_MODES.iter().position(|&m| m == mode).unwrap_or(0),
+            };
+            let mut projectile_colors_idx =
+                PROJECTILE_COLOR_MODES.iter().position(|&m| m == projectile_color_mode).unwrap_or(0);
+            // The top of the spawn slider means no cap. Counts are only written back once a
+            // slider moves, so a --max-spawns or --beat-burst above its range survives
+            const SPAWN_SLIDER_MAX: f32 = 32.0;
+            let shown_spawns = max_spawns_per_frame.map_or(SPAWN_SLIDER_MAX, |n| (n as f32).min(SPAWN_SLIDER_MAX));
+            let mut spawns = shown_spawns;
+            let shown_burst = (beat_burst as f32).min(SPAWN_SLIDER_MAX);
+            let mut burst = shown_burst;
+            widgets::Window::new(hash!(), vec2(20.0, 20.0), vec2(340.0, 400.0))
+                .label("Settings (F2)")
+                .ui(&mut root_ui(), |ui| {
+                    ui.slider(hash!(), "Gain", 0.1..8.0, &mut gain);
+                    ui.slider(hash!(), "Color gamma", 0.2..3.0, &mut color_gamma);
+                    ui.slider(hash!(), "Attack (s)", 0.0..0.5, &mut attack);
+                    ui.slider(hash!(), "Decay (s)", 0.01..2.0, &mut release);
+                    ui.slider(hash!(), "Intensity", MIN_INTENSITY..MAX_INTENSITY, &mut intensity);
+                    ui.slider(hash!(), "Dead zone", 0.0..MAX_DEAD_ZONE, &mut dead_zone);
+                    ui.slider(hash!(), "Bar gap", 0.0..MAX_BAR_GAP, &mut bar_gap);
+                    ui.slider(hash!(), "Transient", 0.0..0.5, &mut state.transient_threshold);
+                    ui.slider(hash!(), "Peak hysteresis", 0.0..0.5, &mut state.peak_hysteresis);
+                    ui.slider(hash!(), "Max spawns (32 = all)", 1.0..SPAWN_SLIDER_MAX, &mut spawns);
+                    ui.slider(hash!(), "Beat burst", 1.0..SPAWN_SLIDER_MAX, &mut burst);
+                    ui.separator();
+                    ui.combo_box(hash!(), "Bar colors", &bar_color_names, &mut bar_colors_idx);
+                    ui.combo_box(
+                        hash!(),
+                        "Projectiles",
+                        &["rainbow", "fixed", "band", "bar"],
+                        &mut projectile_colors_idx,
+                    );
+                    ui.separator();
+                    ui.checkbox(hash!(), "Rotate", &mut rotating);
+                    ui.checkbox(hash!(), "Auto-rotate", &mut auto_rotate);
+                    ui.checkbox(hash!(), "Beats only", &mut beats_only);
+                    ui.checkbox(hash!(), "dB scale", &mut db_scale);
+                    ui.checkbox(hash!(), "Mirror projectiles", &mut mirror_projectiles);
+                    ui.checkbox(hash!(), "Gravity well", &mut gravity_well);
+                });
+            if (attack, release) != (state.attack_time, state.release_time) {
+                state.set_envelope(attack, release);
+            }
+            if spawns != shown_spawns {
+                let n = spawns.round();
+                max_spawns_per_frame = (n < SPAWN_SLIDER_MAX).then_some(n as usize);
+            }
+            if burst != shown_burst {
+                beat_burst = burst.round().max(1.0) as usize;
             }
+            bar_colors = BAR_COLOR_MODES
+                .get(bar_colors_idx)
+                .copied()
+                .unwrap_or_else(|| BarColors::Custom(bar_colors_idx - BAR_COLOR_MODES.len()));
+            projectile_color_mode = PROJECTILE_COLOR_MODES[projectile_colors_idx];
+        }
+        if show_note {
+            let latest = state.dominant_freq().filter(|_| state.energy > 0.01);
+            note_freq = match (note_freq, latest) {
+                // Within a semitone, ease toward the new pitch so the cents don't flicker
+                (Some(prev), Some(f)) if (f / prev).log2().abs() < 1.0 / 12.0 => {
+                    Some(prev * (f / prev).powf(1.0 - (-dt / NOTE_SMOOTHING).exp()))
+                }
+                (_, latest) => latest,
+            };
+            let text = match note_freq {
+                Some(f) => {
+                    let (name, cents) = freq_to_note(f, config.reference_pitch);
+                    format!("{} {:+.0}¢  ({:.1} Hz)", name, cents, f)
+                }
+                None => "--".to_string(),
+            };
+            let cents = note_freq.map_or(50.0, |f| freq_to_note(f, config.reference_pitch).1.abs());
+            let color = if cents < 5.0 {
+                GREEN
+            } else if cents < 15.0 {
+                YELLOW
+            } else {
+                WHITE
+            };
+            let size = 28.0 * ui;
+            let width = measure_text(&text, None, size as u16, 1.0).width;
+            draw_text(&text, (screen_width - width) / 2.0, 64.0 * ui, size, color);
+        }
+        if config.selftest {
+            draw_text("SELF TEST", screen_width / 2.0 - 60.0 * ui, 30.0 * ui, 28.0 * ui, YELLOW);
         }
 
+        if let Some(max_fps) = config.max_fps {
+            // Sleep off whatever the frame budget has left; vsync may already have used it up
+            let budget = Duration::from_secs_f32(1.0 / max_fps);
+            if let Some(left) = budget.checked_sub(frame_start.elapsed()) {
+                thread::sleep(left);
+            }
+            frame_start = std::time::Instant::now();
+        }
         next_frame().await
     }
 }
 
-fn capture_audio(tx: mpsc::Sender<Vec<f32>>, frames_received: Arc<AtomicU64>) {
+/// Logarithmic sine sweep (20 Hz to 20 kHz), the synthetic source for `--selftest`.
+struct SineSweep {
+    phase: f32,
+    elapsed: f32,
+    duration: f32,
+}
+
+impl SineSweep {
+    fn new(duration: f32) -> Self {
+        Self {
+            phase: 0.0,
+            elapsed: 0.0,
+            duration,
+        }
+    }
+
+    /// Next FFT_SIZE samples of the sweep; restarts at 20 Hz after `duration` seconds.
+    fn next_chunk(&mut self) -> Vec<f32> {
+        let dt = 1.0 / SAMPLE_RATE as f32;
+        (0..FFT_SIZE)
+            .map(|_| {
+                let freq = 20.0 * 1000f32.powf(self.elapsed / self.duration);
+                self.phase = (self.phase + std::f32::consts::TAU * freq * dt) % std::f32::consts::TAU;
+                self.elapsed += dt;
+                if self.elapsed >= self.duration {
+                    self.elapsed = 0.0;
+                }
+                0.5 * self.phase.sin()
+            })
+            .collect()
+    }
+}
+
+/// Reduces the magnitudes of the bins under one bar to a single level.
+fn reduce_band(band: &[f32], mode: BandReduce) -> f32 {
+    let band_max = band.iter().fold(0.0f32, |a, &m| a.max(m));
+    let n = band.len().max(1) as f32;
+    match mode {
+        BandReduce::Rms => (band.iter().map(|m| m * m).sum::<f32>() / n).sqrt(),
+        BandReduce::Mix => band_max * 0.4 + band.iter().sum::<f32>() / n * 0.6,
+        BandReduce::Max => band_max,
+    }
+}
+
+/// Starts the configured audio source (self test, stdin, UDP, a `--serve` instance or capture)
+/// on its own thread, sending FFT_SIZE mono chunks to `tx`.
+fn spawn_audio_source(
+    config: &Config,
+    tx: mpsc::Sender<Vec<f32>>,
+    frames_received: &Arc<AtomicU64>,
+    capture_rate: &Arc<AtomicU32>,
+    correlation: &Arc<AtomicU32>,
+) {
+    thread::spawn({
+        let frames = Arc::clone(frames_received);
+        let rate = Arc::clone(capture_rate);
+        let correlation = Arc::clone(correlation);
+        let selftest = config.selftest;
+        let source = CaptureSource {
+            latency: config.latency,
+            // JACK has no loopback; its input ports are patched to whatever should be visualized
+            line_in: config.line_in || config.jack,
+            monitor: config.monitor,
+            jack: config.jack,
+            pid: config.pid,
+            process: config.process.clone(),
+        };
+        let stdin_format = config.stdin.then_some((config.stdin_channels, config.stdin_rate));
+        let listen = config.listen.map(|port| (port, config.stdin_rate));
+        let connect = config.connect.clone();
+        move || {
+            if selftest {
+                run_selftest(tx, frames, rate)
+            } else if let Some((channels, stdin_rate)) = stdin_format {
+                read_stdin_pcm(tx, frames, rate, correlation, channels, stdin_rate)
+            } else if let Some((port, pcm_rate)) = listen {
+                receive_udp_pcm(tx, frames, rate, correlation, port, pcm_rate)
+            } else if let Some(addr) = connect {
+                receive_tcp_audio(tx, frames, rate, addr)
+            } else {
+                capture_audio(tx, frames, rate, correlation, source)
+            }
+        }
+    });
+}
+
+/// Headless capture for a remote visualizer: accepts TCP clients on `port` and streams every
+/// chunk to each of them as a little-endian u32 sample rate, u32 sample count, then the f32 samples.
+fn serve_audio(config: &Config, port: u16) {
+    use std::io::Write;
+
+    let listener = match std::net::TcpListener::bind(("0.0.0.0", port)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Could not listen on TCP port {}: {}", port, e);
+            return;
+        }
+    };
+    eprintln!("Serving audio on TCP port {}", port);
+    let (tx, rx) = mpsc::channel::<Vec<f32>>();
+    let frames_received = Arc::new(AtomicU64::new(0));
+    let capture_rate = Arc::new(AtomicU32::new(0));
+    let correlation = Arc::new(AtomicU32::new(f32::NAN.to_bits()));
+    spawn_audio_source(config, tx, &frames_received, &capture_rate, &correlation);
+
+    // Peer names are kept alongside, since a dead socket can no longer report its address
+    let clients: Arc<Mutex<Vec<(std::net::TcpStream, String)>>> = Arc::new(Mutex::new(Vec::new()));
+    thread::spawn({
+        let clients = Arc::clone(&clients);
+        move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        let peer = stream.peer_addr().map_or_else(|_| "unknown".into(), |addr| addr.to_string());
+                        eprintln!("Visualizer connected from {}", peer);
+                        // A stalled client would otherwise hold up every other one
+                        stream.set_write_timeout(Some(Duration::from_secs(1))).ok();
+                        stream.set_nodelay(true).ok();
+                        if let Ok(mut clients) = clients.lock() {
+                            clients.push((stream, peer));
+                        }
+                    }
+                    Err(e) => eprintln!("TCP accept error: {}", e),
+                }
+            }
+        }
+    });
+
+    let mut frame: Vec<u8> = Vec::with_capacity(8 + 4 * FFT_SIZE);
+    for chunk in rx {
+        frame.clear();
+        frame.extend_from_slice(&capture_rate.load(Ordering::Relaxed).to_le_bytes());
+        frame.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+        for s in &chunk {
+            frame.extend_from_slice(&s.to_le_bytes());
+        }
+        if let Ok(mut clients) = clients.lock() {
+            clients.retain_mut(|(client, peer)| match client.write_all(&frame) {
+                Ok(()) => true,
+                Err(e) => {
+                    eprintln!("Visualizer {} disconnected: {}", peer, e);
+                    false
+                }
+            });
+        }
+    }
+}
+
+/// Receives chunks from a `--serve` instance at `addr`, reconnecting every 2 s while the
+/// server is unreachable or after the connection drops.
+fn receive_tcp_audio(
+    tx: mpsc::Sender<Vec<f32>>,
+    frames_received: Arc<AtomicU64>,
+    sample_rate: Arc<AtomicU32>,
+    addr: String,
+) {
+    loop {
+        match stream_tcp_audio(&tx, &frames_received, &sample_rate, &addr) {
+            Ok(()) => return,
+            Err(e) => {
+                eprintln!("Connection to {} lost: {}, reconnecting in 2s...", addr, e);
+                thread::sleep(Duration::from_secs(2));
+            }
+        }
+    }
+}
+
+/// Largest frame accepted from a server; anything bigger means the stream is out of sync.
+const MAX_TCP_FRAME_SAMPLES: usize = 1 << 20;
+
+/// Reads frames from one connection until it fails (`Err`) or the visualizer goes away (`Ok`).
+/// Frames are read whole, so partial TCP reads never reach the analyzer.
+fn stream_tcp_audio(
+    tx: &mpsc::Sender<Vec<f32>>,
+    frames_received: &AtomicU64,
+    sample_rate: &AtomicU32,
+    addr: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Read;
+
+    let mut reader = std::io::BufReader::new(std::net::TcpStream::connect(addr)?);
+    let mut header = [0u8; 8];
+    let mut bytes: Vec<u8> = Vec::new();
+    let mut mono_buffer: Vec<f32> = Vec::with_capacity(FFT_SIZE * 2);
+    let mut last_rate = 0;
+    loop {
+        reader.read_exact(&mut header)?;
+        let rate = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let count = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        if count > MAX_TCP_FRAME_SAMPLES {
+            return Err(format!("frame of {} samples, stream out of sync", count).into());
+        }
+        bytes.resize(4 * count, 0);
+        reader.read_exact(&mut bytes)?;
+        if rate != last_rate {
+            sample_rate.store(rate, Ordering::Relaxed);
+            log_capture_config("TCP", addr, rate, 1, "f32le");
+            last_rate = rate;
+        }
+        mono_buffer.extend(
+            bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+        );
+        while mono_buffer.len() >= FFT_SIZE {
+            let chunk: Vec<f32> = mono_buffer.drain(..FFT_SIZE).collect();
+            if tx.send(chunk).is_err() {
+                return Ok(());
+            }
+            frames_received.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Reads raw interleaved little-endian f32 PCM from stdin (e.g. `ffmpeg -f f32le -`),
+/// downmixes it and sends FFT_SIZE chunks until stdin closes.
+fn read_stdin_pcm(
+    tx: mpsc::Sender<Vec<f32>>,
+    frames_received: Arc<AtomicU64>,
+    sample_rate: Arc<AtomicU32>,
+    correlation: Arc<AtomicU32>,
+    channels: usize,
+    rate: u32,
+) {
+    use std::io::Read;
+
+    sample_rate.store(rate, Ordering::Relaxed);
+    log_capture_config("stdin", "-", rate, channels, "f32le");
+    let mut stdin = std::io::stdin().lock();
+    let mut bytes = vec![0u8; 4 * channels * 1024];
+    // Bytes of a sample split across reads, carried into the next read
+    let mut pending: Vec<u8> = Vec::with_capacity(4 * channels);
+    let mut mono_buffer: Vec<f32> = Vec::with_capacity(FFT_SIZE * 2);
+    loop {
+        let n = match stdin.read(&mut bytes) {
+            Ok(0) => {
+                eprintln!("stdin closed, no more audio");
+                return;
+            }
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                eprintln!("stdin read error: {}", e);
+                return;
+            }
+        };
+        pending.extend_from_slice(&bytes[..n]);
+        let whole_frames = pending.len() / (4 * channels) * (4 * channels);
+        let samples: Vec<f32> = pending[..whole_frames]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        pending.drain(..whole_frames);
+        store_correlation(&correlation, &samples, channels);
+        mono_buffer.extend(stereo_to_mono_f32(&samples, channels));
+        while mono_buffer.len() >= FFT_SIZE {
+            let chunk: Vec<f32> = mono_buffer.drain(..FFT_SIZE).collect();
+            if tx.send(chunk).is_err() {
+                return;
+            }
+            frames_received.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Receives PCM over UDP on `port` and sends FFT_SIZE chunks. Each datagram is a little-endian
+/// u32 frame count followed by that many frames of interleaved little-endian f32 samples; the
+/// channel count is whatever divides out. Lost packets leave a gap, malformed ones are dropped.
+fn receive_udp_pcm(
+    tx: mpsc::Sender<Vec<f32>>,
+    frames_received: Arc<AtomicU64>,
+    sample_rate: Arc<AtomicU32>,
+    correlation: Arc<AtomicU32>,
+    port: u16,
+    rate: u32,
+) {
+    let socket = match std::net::UdpSocket::bind(("0.0.0.0", port)) {
+        Ok(socket) => socket,
+        Err(e) => {
+            eprintln!("Could not listen on UDP port {}: {}", port, e);
+            return;
+        }
+    };
+    sample_rate.store(rate, Ordering::Relaxed);
+    eprintln!("Listening for PCM on UDP port {}", port);
+    let mut packet = vec![0u8; 65536];
+    let mut mono_buffer: Vec<f32> = Vec::with_capacity(FFT_SIZE * 2);
+    let mut last_channels = 0;
+    let mut malformed_logged = false;
+    loop {
+        let (n, sender) = match socket.recv_from(&mut packet) {
+            Ok(received) => received,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                eprintln!("UDP receive error: {}", e);
+                return;
+            }
+        };
+        let (frames, body) = match packet[..n].split_first_chunk::<4>() {
+            Some((header, body)) => (u32::from_le_bytes(*header) as usize, body),
+            None => (0, &[][..]),
+        };
+        if frames == 0 || body.is_empty() || !body.len().is_multiple_of(4 * frames) {
+            if !malformed_logged {
+                eprintln!("Dropping malformed PCM packet of {} bytes from {} (logged once)", n, sender);
+                malformed_logged = true;
+            }
+            continue;
+        }
+        let channels = body.len() / (4 * frames);
+        if channels != last_channels {
+            log_capture_config("UDP", &sender.to_string(), rate, channels, "f32le");
+            last_channels = channels;
+        }
+        let samples: Vec<f32> = body
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        store_correlation(&correlation, &samples, channels);
+        mono_buffer.extend(stereo_to_mono_f32(&samples, channels));
+        while mono_buffer.len() >= FFT_SIZE {
+            let chunk: Vec<f32> = mono_buffer.drain(..FFT_SIZE).collect();
+            if tx.send(chunk).is_err() {
+                return;
+            }
+            frames_received.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Feeds a real-time sine sweep through the pipeline instead of captured audio,
+/// so bars should light up left to right regardless of the capture backend.
+fn run_selftest(tx: mpsc::Sender<Vec<f32>>, frames_received: Arc<AtomicU64>, sample_rate: Arc<AtomicU32>) {
+    eprintln!("Self test: feeding a 20 Hz - 20 kHz sine sweep instead of captured audio");
+    sample_rate.store(SAMPLE_RATE, Ordering::Relaxed);
+    let chunk_duration = Duration::from_secs_f32(FFT_SIZE as f32 / SAMPLE_RATE as f32);
+    let mut sweep = SineSweep::new(20.0);
+    loop {
+        if tx.send(sweep.next_chunk()).is_err() {
+            return;
+        }
+        frames_received.fetch_add(1, Ordering::Relaxed);
+        thread::sleep(chunk_duration);
+    }
+}
+
+/// Writes the current per-bar center frequencies and levels to `spectrum-<unix time>.csv`
+/// in the working directory and returns the path.
+fn export_spectrum_csv(centers: &[f32; BAR_COUNT], heights: &[f32; BAR_COUNT]) -> std::io::Result<std::path::PathBuf> {
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let path = std::path::PathBuf::from(format!("spectrum-{}.csv", timestamp));
+    let mut csv = String::from("bar,center_hz,level_db\n");
+    for (i, (&hz, &height)) in centers.iter().zip(heights).enumerate() {
+        // Same reference as the dB bar scale: 1.0 = 0 dB, floored at -DB_RANGE
+        let db = (20.0 * height.max(1e-9).log10()).max(-DB_RANGE);
+        csv.push_str(&format!("{},{:.1},{:.1}\n", i, hz, db));
+    }
+    std::fs::write(&path, csv)?;
+    Ok(path)
+}
+
+/// Phase correlation of left/right sample pairs: +1 for mono, 0 for unrelated channels,
+/// -1 for polarity-inverted. `None` if either channel is silent.
+fn stereo_correlation(pairs: impl Iterator<Item = (f32, f32)>) -> Option<f32> {
+    let (mut lr, mut ll, mut rr) = (0.0f32, 0.0f32, 0.0f32);
+    for (l, r) in pairs {
+        lr += l * r;
+        ll += l * l;
+        rr += r * r;
+    }
+    let norm = (ll * rr).sqrt();
+    (norm > 1e-9).then(|| (lr / norm).clamp(-1.0, 1.0))
+}
+
+/// Publishes the correlation of the first two channels of interleaved samples (NaN for mono).
+fn store_correlation(correlation: &AtomicU32, samples: &[f32], channels: usize) {
+    let value = if channels >= 2 {
+        stereo_correlation(samples.chunks_exact(channels).map(|f| (f[0], f[1])))
+    } else {
+        None
+    };
+    correlation.store(value.unwrap_or(f32::NAN).to_bits(), Ordering::Relaxed);
+}
+
+/// Prints the negotiated capture chain in one consistent line, for bug reports.
+fn log_capture_config(backend: &str, device: &str, sample_rate: u32, channels: usize, format: &str) {
+    eprintln!(
+        "Capture: backend={} device=\"{}\" rate={} Hz channels={} format={} fft={} bars={}",
+        backend, device, sample_rate, channels, format, FFT_SIZE, BAR_COUNT
+    );
+}
+
+/// Why a capture backend failed to start or stopped, shared by every platform so callers
+/// can tell failures that need the user apart from ones a retry may fix.
+#[derive(Debug)]
+enum CaptureError {
+    /// Nothing to capture from; says what is missing.
+    NoDevice(String),
+    /// The OS refused access; says which permission to grant and where.
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    PermissionDenied(String),
+    /// The device's sample format or stream configuration can't be opened or decoded.
+    FormatUnsupported(String),
+    /// Any other failure, with the backend's message.
+    Backend(String),
+}
+
+impl std::fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CaptureError::NoDevice(what) => write!(f, "{}", what),
+            CaptureError::PermissionDenied(hint) => write!(f, "permission denied, {}", hint),
+            CaptureError::FormatUnsupported(format) => write!(f, "unsupported format: {}", format),
+            CaptureError::Backend(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {}
+
+impl From<cpal::HostUnavailable> for CaptureError {
+    fn from(e: cpal::HostUnavailable) -> Self {
+        CaptureError::NoDevice(e.to_string())
+    }
+}
+
+impl From<cpal::DefaultStreamConfigError> for CaptureError {
+    fn from(e: cpal::DefaultStreamConfigError) -> Self {
+        match e {
+            cpal::DefaultStreamConfigError::DeviceNotAvailable => CaptureError::NoDevice(e.to_string()),
+            cpal::DefaultStreamConfigError::StreamTypeNotSupported => CaptureError::FormatUnsupported(e.to_string()),
+            _ => CaptureError::Backend(e.to_string()),
+        }
+    }
+}
+
+impl From<cpal::BuildStreamError> for CaptureError {
+    fn from(e: cpal::BuildStreamError) -> Self {
+        match e {
+            cpal::BuildStreamError::DeviceNotAvailable => CaptureError::NoDevice(e.to_string()),
+            cpal::BuildStreamError::StreamConfigNotSupported => CaptureError::FormatUnsupported(e.to_string()),
+            _ => CaptureError::Backend(e.to_string()),
+        }
+    }
+}
+
+impl From<cpal::PlayStreamError> for CaptureError {
+    fn from(e: cpal::PlayStreamError) -> Self {
+        match e {
+            cpal::PlayStreamError::DeviceNotAvailable => CaptureError::NoDevice(e.to_string()),
+            _ => CaptureError::Backend(e.to_string()),
+        }
+    }
+}
+
+/// Logs a failed capture attempt and returns how long to wait before retrying. A denied
+/// permission won't change until the user acts, so those retry less eagerly.
+fn capture_retry_delay(backend: &str, e: &CaptureError) -> Duration {
+    let delay = match e {
+        CaptureError::PermissionDenied(_) => Duration::from_secs(10),
+        _ => Duration::from_secs(2),
+    };
+    eprintln!("{} capture error: {}, reinitializing in {}s...", backend, e, delay.as_secs());
+    delay
+}
+
+/// The command-line options that pick and open the platform capture source.
+#[derive(Clone)]
+struct CaptureSource {
+    latency: Latency,
+    /// Capture the default input device instead of the output's loopback.
+    line_in: bool,
+    /// Also play line-in audio back through the default output.
+    monitor: bool,
+    /// Open cpal's JACK host instead of the platform default.
+    jack: bool,
+    /// Windows process loopback target, by PID or by executable name.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pid: Option<u32>,
+    #[cfg_attr(not(windows), allow(dead_code))]
+    process: Option<String>,
+}
+
+fn capture_audio(
+    tx: mpsc::Sender<Vec<f32>>,
+    frames_received: Arc<AtomicU64>,
+    sample_rate: Arc<AtomicU32>,
+    correlation: Arc<AtomicU32>,
+    source: CaptureSource,
+) {
+    // Line-in goes through cpal's default input device on every platform
+    if source.line_in {
+        capture_audio_cpal(tx, frames_received, sample_rate, correlation, source);
+        return;
+    }
+
     #[cfg(windows)]
-    capture_windows::capture_loopback(tx, frames_received);
+    capture_windows::capture_loopback(
+        tx,
+        frames_received,
+        sample_rate,
+        correlation,
+        source.latency,
+        source.pid,
+        source.process.as_deref(),
+    );
 
+    // ScreenCaptureKit has no buffer-size knob, so `latency` only applies to the fallback.
+    // CoreAudio has no loopback; the next best source is the default input, e.g. an aggregate device.
     #[cfg(target_os = "macos")]
-    capture_macos_sck::capture_loopback(tx, frames_received);
+    {
+        capture_macos_sck::capture_loopback(
+            tx.clone(),
+            Arc::clone(&frames_received),
+            Arc::clone(&sample_rate),
+            Arc::clone(&correlation),
+        );
+        eprintln!("ScreenCaptureKit unavailable, falling back to cpal's default input device");
+        let fallback = CaptureSource { line_in: true, monitor: false, ..source };
+        capture_audio_cpal(tx, frames_received, sample_rate, correlation, fallback);
+    }
 
     #[cfg(all(not(windows), not(target_os = "macos")))]
-    capture_audio_cpal(tx, frames_received);
-}
-
-#[cfg(all(not(windows), not(target_os = "macos")))]
-fn capture_audio_cpal(tx: mpsc::Sender<Vec<f32>>, frames_received: Arc<AtomicU64>) {
-    let host = cpal::default_host();
-    let device = host
-        .default_output_device()
-        .expect("No default output device available");
-    let config = device
-        .default_output_config()
-        .expect("Failed to get output config");
+    capture_audio_cpal(tx, frames_received, sample_rate, correlation, CaptureSource { monitor: false, ..source });
+}
+
+/// Captures through cpal; with `line_in` from the default input device, and with
+/// `monitor` also plays the captured audio back through the default output. On stream
+/// errors (e.g. the device was unplugged), rebuilds against the current default device.
+fn capture_audio_cpal(
+    tx: mpsc::Sender<Vec<f32>>,
+    frames_received: Arc<AtomicU64>,
+    sample_rate: Arc<AtomicU32>,
+    correlation: Arc<AtomicU32>,
+    source: CaptureSource,
+) {
+    loop {
+        match run_cpal_capture(&tx, &frames_received, &sample_rate, &correlation, &source) {
+            Ok(()) => return,
+            Err(e) => thread::sleep(capture_retry_delay("cpal", &e)),
+        }
+    }
+}
+
+/// The cpal host to capture through: JACK with `jack`, otherwise the platform default.
+/// Config parsing rejects `--jack` on builds without the JACK host.
+fn cpal_host(jack: bool) -> Result<cpal::Host, CaptureError> {
+    if jack {
+        #[cfg(all(
+            feature = "jack",
+            any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd", target_os = "netbsd")
+        ))]
+        return Ok(cpal::host_from_id(cpal::HostId::Jack)?);
+    }
+    Ok(cpal::default_host())
+}
+
+/// Runs one cpal capture stream until it errors (`Err`) or the visualizer goes away (`Ok`).
+fn run_cpal_capture(
+    tx: &mpsc::Sender<Vec<f32>>,
+    frames_received: &Arc<AtomicU64>,
+    sample_rate: &AtomicU32,
+    correlation: &Arc<AtomicU32>,
+    source: &CaptureSource,
+) -> Result<(), CaptureError> {
+    let host = cpal_host(source.jack)?;
+    let (device, config) = if source.line_in {
+        // The JACK host only has an input device while its server is running
+        let missing = if host.id().name() == "JACK" {
+            "no JACK input client, is the JACK server running?"
+        } else {
+            "no default input device available"
+        };
+        let device = host.default_input_device().ok_or_else(|| CaptureError::NoDevice(missing.into()))?;
+        let config = device.default_input_config()?;
+        (device, config)
+    } else {
+        let device = host
+            .default_output_device()
+            .ok_or_else(|| CaptureError::NoDevice("no default output device available".into()))?;
+        let config = device.default_output_config()?;
+        (device, config)
+    };
+    sample_rate.store(config.sample_rate().0, Ordering::Relaxed);
     let sample_format = config.sample_format();
     let channels = config.channels() as usize;
-    let stream_config: cpal::StreamConfig = config.into();
+    log_capture_config(
+        match (host.id().name(), source.line_in) {
+            ("JACK", _) => "cpal JACK",
+            (_, true) => "cpal line-in",
+            _ => "cpal",
+        },
+        &device.name().unwrap_or_else(|_| "Unknown".into()),
+        config.sample_rate().0,
+        channels,
+        &format!("{:?}", sample_format),
+    );
+    let supported_buffer = *config.buffer_size();
+    let mut stream_config: cpal::StreamConfig = config.into();
+    if let Some(frames) = source.latency.cpal_frames() {
+        // A size outside the device's range would fail every rebuild, so keep to what it allows
+        stream_config.buffer_size = match supported_buffer {
+            cpal::SupportedBufferSize::Range { min, max } => {
+                let clamped = frames.clamp(min, max);
+                if clamped != frames {
+                    eprintln!("Device buffers are {}-{} frames, using {} instead of {}", min, max, clamped, frames);
+                }
+                cpal::BufferSize::Fixed(clamped)
+            }
+            cpal::SupportedBufferSize::Unknown => cpal::BufferSize::Fixed(frames),
+        };
+    }
+    let monitor_queue = source.monitor.then(|| Arc::new(Mutex::new(VecDeque::<f32>::new())));
+    let _monitor_stream = monitor_queue
+        .as_ref()
+        .and_then(|queue| start_monitor(&host, Arc::clone(queue), stream_config.sample_rate.0, channels));
+    // Cap the monitor backlog at 200 ms so playback can't drift behind the visuals
+    let max_queued = stream_config.sample_rate.0 as usize / 5 * channels;
     let mut sample_buffer: Vec<f32> = Vec::with_capacity(1024);
     const SAMPLES_NEEDED: usize = FFT_SIZE;
-    let err_fn = |err| eprintln!("Audio error: {}", err);
+    // Set from the callbacks; polled below to tear the stream down
+    let failed = Arc::new(AtomicBool::new(false));
+    let closed = Arc::new(AtomicBool::new(false));
+    let err_fn = {
+        let failed = Arc::clone(&failed);
+        move |err| {
+            eprintln!("Audio error: {}", err);
+            failed.store(true, Ordering::Relaxed);
+        }
+    };
 
     macro_rules! build_stream {
         ($fmt:ty, $convert:expr) => {{
-            let frames = Arc::clone(&frames_received);
-            let stream = device
-                .build_input_stream(
-                    &stream_config,
-                    move |data: &[$fmt], _: &cpal::InputCallbackInfo| {
-                        let f32_samples: Vec<f32> = data.iter().map($convert).collect();
-                        let mut samples = stereo_to_mono_f32(&f32_samples, channels);
-                        sample_buffer.append(&mut samples);
-                        while sample_buffer.len() >= SAMPLES_NEEDED {
-                            let chunk: Vec<f32> =
-                                sample_buffer.drain(..SAMPLES_NEEDED).collect();
-                            let peak = chunk.iter().map(|s| s.abs()).fold(0.0f32, f32::max);
-                            let _ = tx.send(chunk);
-                            if peak >= 1e-6 {
-                                frames.fetch_add(1, Ordering::Relaxed);
-                            }
+            let frames = Arc::clone(frames_received);
+            let correlation = Arc::clone(correlation);
+            let tx = tx.clone();
+            let closed = Arc::clone(&closed);
+            let stream = device.build_input_stream(
+                &stream_config,
+                move |data: &[$fmt], _: &cpal::InputCallbackInfo| {
+                    let f32_samples: Vec<f32> = data.iter().map($convert).collect();
+                    store_correlation(&correlation, &f32_samples, channels);
+                    if let Some(queue) = &monitor_queue {
+                        if let Ok(mut queue) = queue.lock() {
+                            queue.extend(&f32_samples);
+                            let excess = queue.len().saturating_sub(max_queued);
+                            queue.drain(..excess);
                         }
-                    },
-                    err_fn,
-                    None,
-                )
-                .expect("Failed to build audio stream");
-            stream.play().expect("Failed to start audio stream");
+                    }
+                    let mut samples = stereo_to_mono_f32(&f32_samples, channels);
+                    sample_buffer.append(&mut samples);
+                    while sample_buffer.len() >= SAMPLES_NEEDED {
+                        let chunk: Vec<f32> = sample_buffer.drain(..SAMPLES_NEEDED).collect();
+                        let peak = chunk.iter().map(|s| s.abs()).fold(0.0f32, f32::max);
+                        if tx.send(chunk).is_err() {
+                            closed.store(true, Ordering::Relaxed);
+                            return;
+                        }
+                        if peak >= 1e-6 {
+                            frames.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                },
+                err_fn,
+                None,
+            )?;
+            stream.play()?;
+            stream
         }};
     }
 
-    match sample_format {
+    // Streams stop when dropped, so keep it alive until it fails
+    let _stream = match sample_format {
         cpal::SampleFormat::F32 => build_stream!(f32, |&s| f32::from_sample(s)),
         cpal::SampleFormat::I16 => build_stream!(i16, |&s| f32::from_sample(s)),
         cpal::SampleFormat::U16 => build_stream!(u16, |&s| f32::from_sample(s)),
         cpal::SampleFormat::I32 => build_stream!(i32, |&s| f32::from_sample(s)),
-        fmt => panic!("Unsupported sample format: {:?}", fmt),
-    }
+        fmt => return Err(CaptureError::FormatUnsupported(format!("{:?}", fmt))),
+    };
 
     loop {
         thread::sleep(Duration::from_millis(100));
+        if closed.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        if failed.load(Ordering::Relaxed) {
+            return Err(CaptureError::Backend("stream stopped".into()));
+        }
     }
 }
 
-#[cfg(all(not(windows), not(target_os = "macos")))]
+/// Opens the default output at the capture rate and plays interleaved samples from
+/// `queue`. Returns `None`, after saying why, if the output can't be opened.
+fn start_monitor(
+    host: &cpal::Host,
+    queue: Arc<Mutex<VecDeque<f32>>>,
+    sample_rate: u32,
+    in_channels: usize,
+) -> Option<cpal::Stream> {
+    let Some(device) = host.default_output_device() else {
+        eprintln!("Monitor disabled: no default output device");
+        return None;
+    };
+    let out_config = match device.default_output_config() {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Monitor disabled: {}", e);
+            return None;
+        }
+    };
+    let out_channels = out_config.channels() as usize;
+    // Same rate as the capture so nothing needs resampling
+    let stream_config = cpal::StreamConfig {
+        channels: out_config.channels(),
+        sample_rate: cpal::SampleRate(sample_rate),
+        buffer_size: cpal::BufferSize::Default,
+    };
+    let err_fn = |err| eprintln!("Monitor error: {}", err);
+
+    macro_rules! build_monitor {
+        ($fmt:ty) => {
+            device.build_output_stream(
+                &stream_config,
+                move |data: &mut [$fmt], _: &cpal::OutputCallbackInfo| {
+                    let Ok(mut queue) = queue.lock() else {
+                        return;
+                    };
+                    for frame in data.chunks_mut(out_channels) {
+                        // Underruns play silence; extra output channels repeat the last input channel
+                        if queue.len() < in_channels {
+                            frame.fill(<$fmt>::from_sample(0.0f32));
+                            continue;
+                        }
+                        for (c, out) in frame.iter_mut().enumerate() {
+                            *out = <$fmt>::from_sample(queue[c.min(in_channels - 1)]);
+                        }
+                        queue.drain(..in_channels);
+                    }
+                },
+                err_fn,
+                None,
+            )
+        };
+    }
+
+    let stream = match out_config.sample_format() {
+        cpal::SampleFormat::F32 => build_monitor!(f32),
+        cpal::SampleFormat::I16 => build_monitor!(i16),
+        cpal::SampleFormat::U16 => build_monitor!(u16),
+        cpal::SampleFormat::I32 => build_monitor!(i32),
+        fmt => {
+            eprintln!("Monitor disabled: unsupported output format {:?}", fmt);
+            return None;
+        }
+    };
+    let stream = match stream {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Monitor disabled: {}", e);
+            return None;
+        }
+    };
+    if let Err(e) = stream.play() {
+        eprintln!("Monitor disabled: {}", e);
+        return None;
+    }
+    eprintln!(
+        "Monitor: device=\"{}\" rate={} Hz channels={}",
+        device.name().unwrap_or_else(|_| "Unknown".into()),
+        sample_rate,
+        out_channels
+    );
+    Some(stream)
+}
+
 fn stereo_to_mono_f32(samples: &[f32], channels: usize) -> Vec<f32> {
     if channels <= 1 {
         return samples.to_vec();
     }
+    // A trailing partial frame is averaged over the samples it actually has
     samples
         .chunks(channels)
-        .map(|chunk| chunk.iter().sum::<f32>() / channels as f32)
+        .map(|chunk| chunk.iter().sum::<f32>() / chunk.len() as f32)
         .collect()
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(freq: f32, amplitude: f32) -> Vec<f32> {
+        (0..FFT_SIZE)
+            .map(|i| amplitude * (std::f32::consts::TAU * freq * i as f32 / SAMPLE_RATE as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn reset_bars_restores_initial_state() {
+        let mut state = VisualizerState::new();
+        // Quiet chunk first so the tone registers as an onset and fires peaks
+        state.update(&vec![0.0; FFT_SIZE]);
+        for _ in 0..20 {
+            state.update(&tone(1000.0, 0.4));
+        }
+        assert!(state.bar_heights.iter().any(|&h| h > 0.0));
+        assert!(!state.peak_fired.is_empty());
+
+        state.reset_bars();
+        let fresh = VisualizerState::new();
+        assert_eq!(state.bar_heights, fresh.bar_heights);
+        assert_eq!(state.peak_heights, fresh.peak_heights);
+        assert_eq!(state.fire_cooldown, fresh.fire_cooldown);
+        assert_eq!(state.bar_flash, fresh.bar_flash);
+        assert!(state.peak_fired.is_empty());
+        assert_eq!(state.peak_magnitude, fresh.peak_magnitude);
+        assert!(state.agc_history.is_empty());
+        assert_eq!(state.energy, fresh.energy);
+        assert!(state.magnitudes.iter().all(|&m| m == 0.0));
+        assert!(state.prev_magnitudes.iter().all(|&m| m == 0.0));
+        assert_eq!(state.flux, fresh.flux);
+        assert_eq!(state.flux_avg, fresh.flux_avg);
+        assert_eq!(state.pulse, fresh.pulse);
+    }
+
+    #[test]
+    fn envelope_rises_and_falls_at_separate_rates() {
+        let loudest = |state: &VisualizerState| state.bar_heights.iter().fold(0.0f32, |a, &h| a.max(h));
+        let mut fast_attack = VisualizerState::new();
+        fast_attack.set_envelope(0.0, 1.0);
+        let mut fast_release = VisualizerState::new();
+        fast_release.set_envelope(1.0, 0.0);
+        for state in [&mut fast_attack, &mut fast_release] {
+            state.update(&vec![0.0; FFT_SIZE]);
+            state.update(&tone(1000.0, 0.4));
+        }
+        let risen = loudest(&fast_attack);
+        assert!(risen > 0.1);
+        assert!(risen > 10.0 * loudest(&fast_release));
+
+        for state in [&mut fast_attack, &mut fast_release] {
+            state.update(&vec![0.0; FFT_SIZE]);
+        }
+        assert!(loudest(&fast_attack) > 0.9 * risen);
+        assert!(loudest(&fast_release) < 1e-3);
+    }
+
+    #[test]
+    fn band_reduce_modes_on_multi_tone() {
+        // Exact values on a hand-made band: max 4, mean 1.75, mean power 25/4
+        assert_eq!(reduce_band(&[3.0, 4.0, 0.0, 0.0], BandReduce::Max), 4.0);
+        assert_eq!(reduce_band(&[3.0, 4.0, 0.0, 0.0], BandReduce::Mix), 4.0 * 0.4 + 1.75 * 0.6);
+        assert_eq!(reduce_band(&[3.0, 4.0, 0.0, 0.0], BandReduce::Rms), 2.5);
+
+        let signal: Vec<f32> = tone(150.0, 0.2)
+            .iter()
+            .zip(tone(2000.0, 0.2))
+            .map(|(a, b)| a + b)
+            .collect();
+        let heights = |mode| {
+            let mut state = VisualizerState::new();
+            state.band_reduce = mode;
+            state.set_envelope(0.0, 0.0);
+            state.update(&signal);
+            state.bar_heights
+        };
+        let (rms, mix, max) = (heights(BandReduce::Rms), heights(BandReduce::Mix), heights(BandReduce::Max));
+        for bars in [&rms, &mix, &max] {
+            let lit: Vec<usize> = (0..BAR_COUNT).filter(|&i| bars[i] > 0.03).collect();
+            assert_eq!(lit, [6, 41]);
+        }
+        // The tones sit in a few bins of each band, so spreading them over the band lowers the level
+        for i in [6, 41] {
+            assert!(max[i] > mix[i] && mix[i] > rms[i], "bar {}: {} {} {}", i, max[i], mix[i], rms[i]);
+        }
+    }
+
+    #[test]
+    fn band_bins_are_contiguous_and_never_empty() {
+        for rate in [44100, 48000, 96000] {
+            let mut state = VisualizerState::new();
+            state.set_sample_rate(rate);
+            let mut prev: Option<(usize, usize)> = None;
+            for i in 0..BAR_COUNT {
+                let (start, end) = state.band_bins(i);
+                assert!(start < end && end <= FFT_SIZE / 2, "bar {} at {} Hz: {}..{}", i, rate, start, end);
+                if let Some((prev_start, prev_end)) = prev {
+                    // No bins skipped; the only overlap is a too-narrow bar widened to one bin
+                    assert!(start >= prev_start && end >= prev_end, "bar {} at {} Hz goes backwards", i, rate);
+                    assert!(start <= prev_end, "bar {} at {} Hz skips bins {}..{}", i, rate, prev_end, start);
+                    assert!(start + 1 >= prev_end, "bar {} at {} Hz overlaps bar {}", i, rate, i - 1);
+                }
+                prev = Some((start, end));
+            }
+        }
+    }
+
+    #[test]
+    fn band_bins_put_bass_low_and_highs_high() {
+        let state = VisualizerState::new();
+        let freq_per_bin = state.sample_rate / FFT_SIZE as f32;
+        let bass = state.bands.bass_bars;
+        // The whole bass region sits below the bass/mid edge, the top bar reaches past 10 kHz
+        assert!(state.band_bins(bass - 1).1 as f32 * freq_per_bin <= state.bands.bass_mid_hz);
+        assert!(state.band_bins(0).0 as f32 * freq_per_bin < 50.0);
+        assert!(state.band_bins(BAR_COUNT - 1).1 as f32 * freq_per_bin > 10_000.0);
+    }
+
+    #[test]
+    fn decelerating_projectiles_ease_onto_the_speed_floor() {
+        let floor = 90.0;
+        let rate = 2.5 / 60.0;
+        let mut speed = 400.0;
+        let mut last_drop = f32::INFINITY;
+        for _ in 0..600 {
+            let next = projectile_speed(speed, rate, true, floor);
+            let drop = speed - next;
+            assert!(next >= floor, "speed {} fell below the floor", next);
+            assert!(drop >= 0.0, "decelerating projectile sped up from {} to {}", speed, next);
+            // The slowdown only ever tapers, so there's no kink where the floor takes over
+            assert!(drop <= last_drop + 1e-4, "slowdown grew from {} to {} at {}", last_drop, drop, speed);
+            last_drop = drop;
+            speed = next;
+        }
+        assert!(speed < floor * 1.1, "never approached the floor, still at {}", speed);
+        // Slower launches are lifted to the floor, and speeding up is unaffected above it
+        assert_eq!(projectile_speed(40.0, rate, true, floor), floor);
+        assert_eq!(projectile_speed(200.0, 0.5, false, floor), 300.0);
+    }
+
+    #[test]
+    fn freq_to_note_names_notes_around_a440() {
+        let (name, cents) = freq_to_note(440.0, 440.0);
+        assert_eq!(name, "A4");
+        assert!(cents.abs() < 0.01);
+        // Equal-tempered neighbors and octaves keep the right names and octave numbers
+        for (hz, expected) in [(466.1638, "A#4"), (415.3047, "G#4"), (261.6256, "C4"), (246.9417, "B3"), (880.0, "A5"), (27.5, "A0")] {
+            let (name, cents) = freq_to_note(hz, 440.0);
+            assert_eq!(name, expected, "{} Hz", hz);
+            assert!(cents.abs() < 0.1, "{} Hz: {} cents", hz, cents);
+        }
+    }
+
+    #[test]
+    fn freq_to_note_reports_cents_and_reference() {
+        // 445 Hz is 19.6 cents sharp of A4, 435 Hz 19.8 cents flat
+        let (name, cents) = freq_to_note(445.0, 440.0);
+        assert_eq!(name, "A4");
+        assert!((cents - 19.56).abs() < 0.1, "{}", cents);
+        let (name, cents) = freq_to_note(435.0, 440.0);
+        assert_eq!(name, "A4");
+        assert!((cents + 19.79).abs() < 0.1, "{}", cents);
+        // Just under a quarter tone above A4 is still A4; just over rounds up to A#4
+        assert_eq!(freq_to_note(440.0 * 2f32.powf(0.49 / 12.0), 440.0).0, "A4");
+        assert_eq!(freq_to_note(440.0 * 2f32.powf(0.51 / 12.0), 440.0).0, "A#4");
+        // With A4 = 432 Hz, 432 Hz is in tune and 440 Hz is sharp
+        assert!(freq_to_note(432.0, 432.0).1.abs() < 0.01);
+        let (name, cents) = freq_to_note(440.0, 432.0);
+        assert_eq!(name, "A4");
+        assert!((cents - 31.77).abs() < 0.1, "{}", cents);
+    }
+
+    #[test]
+    fn stereo_to_mono_passes_mono_through() {
+        let samples = [0.1, -0.2, 0.3];
+        assert_eq!(stereo_to_mono_f32(&samples, 1), samples);
+        assert_eq!(stereo_to_mono_f32(&samples, 0), samples);
+    }
+
+    #[test]
+    fn stereo_to_mono_averages_frames() {
+        assert_eq!(stereo_to_mono_f32(&[1.0, 0.0, 0.5, 0.5, -1.0, 1.0], 2), [0.5, 0.5, 0.0]);
+        assert_eq!(stereo_to_mono_f32(&[1.0, 1.0, 0.0, 0.0, 0.5, 0.5, 0.5, 0.5], 4), [0.5, 0.5]);
+    }
+
+    #[test]
+    fn stereo_to_mono_averages_trailing_partial_frame_by_its_length() {
+        assert_eq!(stereo_to_mono_f32(&[0.25, 0.75, 0.5], 2), [0.5, 0.5]);
+        assert_eq!(stereo_to_mono_f32(&[0.0, 0.0, 0.0, 0.0, 0.25, 0.75], 4), [0.0, 0.5]);
+    }
+}